@@ -2,7 +2,7 @@ use futures::pin_mut;
 use futures::stream::StreamExt;
 
 #[tokio::main]
-async fn main() -> failure::Fallible<()> {
+async fn main() -> anyhow::Result<()> {
     let args: Vec<String> = std::env::args().collect();
     let url = args
         .get(1)
@@ -13,16 +13,22 @@ async fn main() -> failure::Fallible<()> {
     let stream = stork_http::HttpStorkable::new(url).exec();
     pin_mut!(stream); // needed for iteration
 
-    while let Some(link) = stream.next().await {
-        let link = link?;
+    while let Some(found) = stream.next().await {
+        // skip over the page itself, and any links we've already seen
+        let link = match found?.into_child() {
+            Some(link) => link,
+            None => continue,
+        };
 
         println!("{:?}", link.val());
 
         let stream = link.exec();
         pin_mut!(stream); // needed for iteration
 
-        while let Some(link) = stream.next().await {
-            println!("> {:?}", link?.val());
+        while let Some(found) = stream.next().await {
+            if let Some(link) = found?.into_child() {
+                println!("> {:?}", link.val());
+            }
         }
     }
 
@@ -0,0 +1,529 @@
+//! A [Crawler] takes care of recursively storking a root [Storkable]
+//! and all of its children so you don't have to keep your own queue
+//! of streams around.
+//!
+//! The order in which [Storkable]s are storked is decided by the
+//! [Frontier] given to the [Crawler]. stork ships with [BreadthFirst],
+//! [DepthFirst] and [BestFirst] implementations but you're free to
+//! implement your own.
+
+use crate::{DedupPolicy, FilterSet, StorkClient, StorkError, Storkable, Storked};
+
+use async_stream::stream;
+use futures::prelude::*;
+use futures::stream::SelectAll;
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::hash::Hash;
+
+/// Holds the [Storkable]s a [Crawler] has found but not yet storked,
+/// and decides which of them should be storked next.
+pub trait Frontier<T: Unpin + PartialEq + Hash, C: StorkClient<T>> {
+    /// Adds a newly discovered [Storkable] to the frontier.
+    fn push(&mut self, storkable: Storkable<T, C>);
+
+    /// Takes the next [Storkable] that should be storked from the
+    /// frontier, or `None` if there's nothing left to stork.
+    fn pop(&mut self) -> Option<Storkable<T, C>>;
+}
+
+/// Storks [Storkable]s in the order they were found, every [Storkable]
+/// at a given depth will be storked before any at the next depth.
+pub struct BreadthFirst<T: Unpin + PartialEq + Hash, C: StorkClient<T>>(VecDeque<Storkable<T, C>>);
+impl<T: Unpin + PartialEq + Hash, C: StorkClient<T>> Default for BreadthFirst<T, C> {
+    fn default() -> Self {
+        Self(VecDeque::new())
+    }
+}
+impl<T: Unpin + PartialEq + Hash, C: StorkClient<T>> Frontier<T, C> for BreadthFirst<T, C> {
+    fn push(&mut self, storkable: Storkable<T, C>) {
+        self.0.push_back(storkable);
+    }
+
+    fn pop(&mut self) -> Option<Storkable<T, C>> {
+        self.0.pop_front()
+    }
+}
+
+/// Storks the most recently found [Storkable] first, following a
+/// branch as deep as it goes before moving on to the next one.
+pub struct DepthFirst<T: Unpin + PartialEq + Hash, C: StorkClient<T>>(Vec<Storkable<T, C>>);
+impl<T: Unpin + PartialEq + Hash, C: StorkClient<T>> Default for DepthFirst<T, C> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+impl<T: Unpin + PartialEq + Hash, C: StorkClient<T>> Frontier<T, C> for DepthFirst<T, C> {
+    fn push(&mut self, storkable: Storkable<T, C>) {
+        self.0.push(storkable);
+    }
+
+    fn pop(&mut self) -> Option<Storkable<T, C>> {
+        self.0.pop()
+    }
+}
+
+/// Scoring function used by [BestFirst] to rank [Storkable]s.
+type Scorer<T, C> = Box<dyn Fn(&Storkable<T, C>) -> i64 + Send>;
+
+/// Storks the [Storkable] with the highest score first, as decided by
+/// the scoring function given to [BestFirst::new]. [Storkable]s with
+/// equal scores are storked in the order they were found.
+pub struct BestFirst<T: Unpin + PartialEq + Hash, C: StorkClient<T>> {
+    queue: BinaryHeap<Scored<T, C>>,
+    scorer: Scorer<T, C>,
+    sequence: u64,
+}
+impl<T: Unpin + PartialEq + Hash, C: StorkClient<T>> BestFirst<T, C> {
+    pub fn new<S: Fn(&Storkable<T, C>) -> i64 + Send + 'static>(scorer: S) -> Self {
+        Self {
+            queue: BinaryHeap::new(),
+            scorer: Box::new(scorer),
+            sequence: 0,
+        }
+    }
+}
+impl<T: Unpin + PartialEq + Hash, C: StorkClient<T>> Frontier<T, C> for BestFirst<T, C> {
+    fn push(&mut self, storkable: Storkable<T, C>) {
+        let score = (self.scorer)(&storkable);
+        self.sequence += 1;
+
+        self.queue.push(Scored {
+            score,
+            sequence: self.sequence,
+            storkable,
+        });
+    }
+
+    fn pop(&mut self) -> Option<Storkable<T, C>> {
+        self.queue.pop().map(|scored| scored.storkable)
+    }
+}
+
+/// Entry in a [BestFirst] queue, ordered by score and then by the
+/// reverse of the order it was pushed in so earlier finds win ties.
+struct Scored<T: Unpin + PartialEq + Hash, C: StorkClient<T>> {
+    score: i64,
+    sequence: u64,
+    storkable: Storkable<T, C>,
+}
+impl<T: Unpin + PartialEq + Hash, C: StorkClient<T>> PartialEq for Scored<T, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl<T: Unpin + PartialEq + Hash, C: StorkClient<T>> Eq for Scored<T, C> {}
+impl<T: Unpin + PartialEq + Hash, C: StorkClient<T>> PartialOrd for Scored<T, C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<T: Unpin + PartialEq + Hash, C: StorkClient<T>> Ord for Scored<T, C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .cmp(&other.score)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+/// Recursively storks a root [Storkable] and every [Storkable] found
+/// beneath it, yielding each one as it's found and again once it's been
+/// storked.
+///
+/// By default the tree is traversed breadth-first with no depth limit,
+/// one [Storkable] at a time. This can be changed using
+/// [Crawler::depth_first], [Crawler::best_first],
+/// [Crawler::with_frontier], [Crawler::max_depth] and
+/// [Crawler::concurrency].
+///
+/// Unlike a lone [Storkable], nothing is storked twice in the same
+/// crawl: the root's [DedupPolicy] is replaced with
+/// [DedupPolicy::PerCrawl], whatever it was set to beforehand. Use
+/// [Crawler::dedup] to pick a different one.
+pub struct Crawler<T: Unpin + PartialEq + Hash, C: StorkClient<T>, F: Frontier<T, C>> {
+    root: Storkable<T, C>,
+    frontier: F,
+    max_depth: Option<usize>,
+    concurrency: usize,
+    follow_filters: FilterSet<T>,
+}
+
+impl<T: Unpin + PartialEq + Hash, C: StorkClient<T>> Crawler<T, C, BreadthFirst<T, C>> {
+    /// Instantiates a new breadth-first [Crawler] starting at the given
+    /// [Storkable], the crawl can then be started using [Crawler::crawl].
+    ///
+    /// The root is set to deduplicate across the whole crawl, see
+    /// [Crawler::dedup].
+    pub fn new(root: Storkable<T, C>) -> Self {
+        Self {
+            root: root.with_dedup(DedupPolicy::PerCrawl),
+            frontier: BreadthFirst::default(),
+            max_depth: None,
+            concurrency: 1,
+            follow_filters: FilterSet::default(),
+        }
+    }
+}
+
+impl<'a, T, C, F> Crawler<T, C, F>
+where
+    T: Unpin + PartialEq + Hash + Clone + 'a,
+    C: StorkClient<T> + 'a,
+    F: Frontier<T, C> + 'a,
+{
+    /// Replaces the [Frontier] used to decide which [Storkable] should
+    /// be storked next.
+    pub fn with_frontier<N: Frontier<T, C>>(self, frontier: N) -> Crawler<T, C, N> {
+        Crawler {
+            root: self.root,
+            frontier,
+            max_depth: self.max_depth,
+            concurrency: self.concurrency,
+            follow_filters: self.follow_filters,
+        }
+    }
+
+    /// Stork the tree breadth-first, this is the default.
+    pub fn breadth_first(self) -> Crawler<T, C, BreadthFirst<T, C>> {
+        self.with_frontier(BreadthFirst::default())
+    }
+
+    /// Stork the tree depth-first.
+    pub fn depth_first(self) -> Crawler<T, C, DepthFirst<T, C>> {
+        self.with_frontier(DepthFirst::default())
+    }
+
+    /// Stork the highest scoring [Storkable] found so far first, see
+    /// [BestFirst].
+    pub fn best_first<S: Fn(&Storkable<T, C>) -> i64 + Send + 'static>(
+        self,
+        scorer: S,
+    ) -> Crawler<T, C, BestFirst<T, C>> {
+        self.with_frontier(BestFirst::new(scorer))
+    }
+
+    /// Stops the crawler from yielding any [Storkable]s with a
+    /// [Storkable::depth] greater than `max_depth`.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Sets how the root and everything found beneath it deduplicate
+    /// the values they yield, replacing the [DedupPolicy::PerCrawl] set
+    /// by [Crawler::new]. See [DedupPolicy].
+    pub fn dedup(mut self, dedup: DedupPolicy) -> Self {
+        self.root = self.root.with_dedup(dedup);
+        self
+    }
+
+    /// Sets the maximum number of [Storkable]s that will be storked at
+    /// once, and therefore the maximum number of in-flight calls to
+    /// [StorkClient::run]. Defaults to 1.
+    ///
+    /// The [Frontier] still decides which [Storkable]s are storked
+    /// next, but with a concurrency above 1 their children will be
+    /// yielded interleaved in whichever order they're found.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Only stork [Storkable]s matching the given filters, anything
+    /// that doesn't match will still be yielded but its children won't
+    /// be looked for. Useful for checking links to other sites without
+    /// crawling them too.
+    pub fn follow_filters(mut self, filters: FilterSet<T>) -> Self {
+        self.follow_filters = filters;
+        self
+    }
+
+    /// Start crawling from the root [Storkable].
+    ///
+    /// Returns a stream of every [Storkable] found whilst crawling, in
+    /// the order they were found, as described by [Storkable::exec]:
+    /// each [Storkable] storked (including the root) is yielded as a
+    /// [Storked::Fetched] ahead of the children found on it. The depth
+    /// each was found at can be grabbed using [Storkable::depth].
+    ///
+    /// Errors from storking a [Storkable] are yielded alongside its
+    /// children according to its [ErrorPolicy](crate::ErrorPolicy),
+    /// the crawl carries on with the rest of the frontier either way.
+    ///
+    /// Like [Storkable::exec], the returned stream is [Send] if the
+    /// [Storkable]s being crawled and the [Frontier] are.
+    pub fn crawl(
+        self,
+    ) -> impl futures::Stream<Item = Result<Storked<T, C>, StorkError<C::Error>>> + 'a {
+        let Crawler {
+            root,
+            mut frontier,
+            max_depth,
+            concurrency,
+            follow_filters,
+        } = self;
+
+        stream! {
+            if max_depth.is_none_or(|max| root.depth() < max) {
+                frontier.push(root);
+            }
+
+            let mut in_flight = SelectAll::new();
+            // how many of the streams in flight haven't finished, SelectAll
+            // only lets go of a stream once it's been polled past its end
+            let mut running = 0;
+
+            loop {
+                // top up the in-flight storkables from the frontier. each
+                // one ends with a `None` so we hear about it finishing
+                // straight away, rather than SelectAll quietly dropping it
+                // and carrying on waiting for the rest
+                while running < concurrency {
+                    match frontier.pop() {
+                        Some(next) => {
+                            in_flight.push(Box::pin(
+                                next.exec()
+                                    .map(Some)
+                                    .chain(stream::once(future::ready(None))),
+                            ));
+                            running += 1;
+                        }
+                        None => break,
+                    }
+                }
+
+                // nothing in flight after topping up means the frontier
+                // is empty too, so there's nothing left to crawl
+                if running == 0 {
+                    break;
+                }
+
+                match in_flight.next().await {
+                    Some(Some(Ok(Storked::Child(child)))) => {
+                        // only queue up children which are able to
+                        // yield something within the depth limit and
+                        // that we've been asked to follow
+                        if max_depth.is_none_or(|max| child.depth() < max)
+                            && follow_filters.matches(child.val())
+                        {
+                            frontier.push(child.clone());
+                        }
+
+                        yield Ok(Storked::Child(child));
+                    }
+                    Some(Some(Ok(found))) => yield Ok(found),
+                    Some(Some(Err(e))) => yield Err(e),
+                    // a storkable finishing frees up its slot, so we'll go
+                    // back round and top up
+                    Some(None) => running -= 1,
+                    None => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::Filter;
+    use crate::tests::Tree;
+    use crate::{ClientStream, Found};
+
+    use std::time::{Duration, Instant};
+
+    /// Matches even numbers.
+    #[derive(Debug, Clone)]
+    struct Even;
+    impl Filter<u32> for Even {
+        fn matches(&self, val: &u32) -> bool {
+            val.is_multiple_of(2)
+        }
+    }
+
+    async fn crawl<F: Frontier<u32, Tree>>(crawler: Crawler<u32, Tree, F>) -> Vec<(u32, usize)> {
+        crawler
+            .crawl()
+            .filter_map(|found| future::ready(found.unwrap().into_child()))
+            .map(|storkable| (*storkable.val(), storkable.depth()))
+            .collect()
+            .await
+    }
+
+    fn values(found: &[(u32, usize)]) -> Vec<u32> {
+        found.iter().map(|(value, _)| *value).collect()
+    }
+
+    #[tokio::test]
+    async fn breadth_first() {
+        let found = crawl(Crawler::new(Storkable::new(1))).await;
+
+        assert_eq!(values(&found), (2..16).collect::<Vec<_>>());
+        // every depth is finished before the next is started
+        assert!(found.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+    }
+
+    #[tokio::test]
+    async fn storked_storkables_are_yielded() {
+        let fetched = Crawler::new(Storkable::<_, Tree>::new(1))
+            .max_depth(3)
+            .crawl()
+            .filter_map(|found| {
+                future::ready(match found.unwrap() {
+                    Storked::Fetched(fetched) => Some((*fetched.val(), *fetched.page().unwrap())),
+                    _ => None,
+                })
+            })
+            .collect::<Vec<_>>()
+            .await;
+
+        // including those without any new children, with their pages
+        assert_eq!(
+            fetched,
+            vec![(1, 4), (2, 4), (3, 4), (4, 4), (5, 4), (6, 4), (7, 4)]
+        );
+    }
+
+    #[tokio::test]
+    async fn depth_first() {
+        let found = crawl(Crawler::new(Storkable::new(1)).depth_first()).await;
+
+        assert_eq!(
+            values(&found),
+            vec![2, 3, 6, 7, 14, 15, 12, 13, 4, 5, 10, 11, 8, 9]
+        );
+    }
+
+    #[tokio::test]
+    async fn best_first() {
+        // odd numbers first, otherwise in the order they were found
+        let found = crawl(
+            Crawler::new(Storkable::new(1)).best_first(|storkable| i64::from(storkable.val() % 2)),
+        )
+        .await;
+
+        assert_eq!(
+            values(&found),
+            vec![2, 3, 6, 7, 14, 15, 4, 5, 10, 11, 12, 13, 8, 9]
+        );
+    }
+
+    #[tokio::test]
+    async fn max_depth() {
+        let found = crawl(Crawler::new(Storkable::new(1)).max_depth(2)).await;
+
+        assert_eq!(values(&found), vec![2, 3, 4, 5, 6, 7]);
+        assert_eq!(found.last().unwrap().1, 2);
+
+        assert!(crawl(Crawler::new(Storkable::new(1)).max_depth(0))
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn follow_filters() {
+        let found = crawl(
+            Crawler::new(Storkable::new(1)).follow_filters(FilterSet::default().add_filter(Even)),
+        )
+        .await;
+
+        // odd numbers are yielded but not followed
+        assert_eq!(values(&found), vec![2, 3, 4, 5, 8, 9]);
+    }
+
+    /// Links 1 to 2 and 3, which both link to 4.
+    #[derive(Debug, Default)]
+    struct Diamond;
+    impl StorkClient<u32> for Diamond {
+        type Error = std::io::Error;
+        type Page = ();
+        type Stream = ClientStream<Found<u32, ()>, std::io::Error>;
+
+        fn run(&self, src: &u32) -> Self::Stream {
+            let children = match src {
+                1 => vec![2, 3],
+                2 | 3 => vec![4],
+                _ => vec![],
+            };
+
+            Box::pin(stream::iter(children.into_iter().map(Found::Child).map(Ok)))
+        }
+    }
+
+    async fn crawl_diamond(crawler: Crawler<u32, Diamond, BreadthFirst<u32, Diamond>>) -> Vec<u32> {
+        crawler
+            .crawl()
+            .filter_map(|found| future::ready(found.unwrap().into_child()))
+            .map(|storkable| *storkable.val())
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn dedup() {
+        // 4 is only storked once by default, however many pages link to it
+        let found = crawl_diamond(Crawler::new(Storkable::new(1))).await;
+        assert_eq!(found, vec![2, 3, 4]);
+
+        let found =
+            crawl_diamond(Crawler::new(Storkable::new(1)).dedup(DedupPolicy::PerParent)).await;
+        assert_eq!(found, vec![2, 3, 4, 4]);
+    }
+
+    #[tokio::test]
+    async fn concurrency_finds_everything() {
+        let mut found = values(&crawl(Crawler::new(Storkable::new(1)).concurrency(4)).await);
+        found.sort_unstable();
+
+        assert_eq!(found, (2..16).collect::<Vec<_>>());
+    }
+
+    /// Storking 1 takes a second and finds nothing, whilst every number
+    /// from 2 to 10 takes a tenth of a second to find the next one.
+    #[derive(Debug, Default)]
+    struct Slow;
+    impl StorkClient<u32> for Slow {
+        type Error = std::io::Error;
+        type Page = ();
+        type Stream = ClientStream<Found<u32, ()>, std::io::Error>;
+
+        fn run(&self, src: &u32) -> Self::Stream {
+            let src = *src;
+
+            Box::pin(stream! {
+                match src {
+                    0 => {
+                        yield Ok(Found::Child(1));
+                        yield Ok(Found::Child(2));
+                    }
+                    1 => tokio::time::delay_for(Duration::from_secs(1)).await,
+                    2..=9 => {
+                        tokio::time::delay_for(Duration::from_millis(100)).await;
+                        yield Ok(Found::Child(src + 1));
+                    }
+                    _ => {}
+                }
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn finished_storkables_free_their_slot() {
+        let started = Instant::now();
+        let found = Crawler::new(Storkable::<_, Slow>::new(0))
+            .concurrency(2)
+            .crawl()
+            .count()
+            .await;
+
+        assert_eq!(found, 10);
+        // the chain from 2 to 10 should be storked alongside 1 rather
+        // than each link in it waiting for 1 to finish
+        assert!(
+            started.elapsed() < Duration::from_millis(1500),
+            "took {:?}",
+            started.elapsed()
+        );
+    }
+}
@@ -1,5 +1,13 @@
-#[derive(Debug, Fail)]
-pub enum StorkError {
-    #[fail(display = "error whilst fetching link from StorkClient")]
-    ClientError,
+/// Boxed error returned by a [SeenStore](crate::SeenStore), which could
+/// be backed by anything.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// An error encountered whilst storking, `E` being the error type of
+/// the [StorkClient](crate::StorkClient) doing the storking.
+#[derive(Debug, thiserror::Error)]
+pub enum StorkError<E: std::error::Error + 'static> {
+    #[error("error whilst fetching link from StorkClient")]
+    ClientError(#[source] E),
+    #[error("error whilst checking if a value has been seen before")]
+    SeenStoreError(#[source] BoxError),
 }
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 /// List of filters that can be used to filter down results from a
 /// [Storkable](crate::Storkable). Once constructed, these can be
 /// attached using [Storkable::with_filters](crate::Storkable::with_filters).
@@ -22,7 +24,7 @@ impl<T> FilterSet<T> {
     pub(crate) fn matches(&self, val: &T) -> bool {
         if let Some(filters) = &self.filters {
             for filter in filters.iter() {
-                if !filter.matches(&val) {
+                if !filter.matches(val) {
                     return false;
                 }
             }
@@ -55,10 +57,12 @@ impl<T> Clone for FilterSet<T> {
 ///
 /// Note: *all* implementations of `Filter` should have an impl of
 /// [Clone] so they can be passed to children and modified without
-/// modifying FilterSets on parents.
+/// modifying FilterSets on parents, and must be thread-safe so they
+/// can be shared between [Storkable](crate::Storkable)s being storked
+/// on different threads.
 ///
 /// [html_filters]: (../stork_html/filters)
-pub trait Filter<T>: std::fmt::Debug + dyn_clone::DynClone {
+pub trait Filter<T>: std::fmt::Debug + dyn_clone::DynClone + Send + Sync {
     fn matches(&self, val: &T) -> bool;
 }
 
@@ -72,10 +76,204 @@ impl<T> std::clone::Clone for Box<dyn Filter<T>> {
     }
 }
 
+/// Matches values which don't match the given filter.
+#[derive(Debug, Clone)]
+pub struct Not<F>(pub F);
+impl<T, F: Filter<T> + Clone> Filter<T> for Not<F> {
+    fn matches(&self, val: &T) -> bool {
+        !self.0.matches(val)
+    }
+}
+
+/// Matches values which match any of its filters, an empty `AnyOf`
+/// matches nothing.
+pub struct AnyOf<T> {
+    filters: Vec<Box<dyn Filter<T>>>,
+}
+impl<T> AnyOf<T> {
+    /// Adds another alternative to match against.
+    pub fn add_filter<F: Filter<T> + 'static>(mut self, filter: F) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+}
+impl<T> Filter<T> for AnyOf<T> {
+    fn matches(&self, val: &T) -> bool {
+        self.filters.iter().any(|filter| filter.matches(val))
+    }
+}
+impl<T> Default for AnyOf<T> {
+    fn default() -> Self {
+        Self {
+            filters: Vec::new(),
+        }
+    }
+}
+/// Manually implemented for the same reason as [FilterSet]'s [Clone].
+impl<T> Clone for AnyOf<T> {
+    fn clone(&self) -> Self {
+        Self {
+            filters: self.filters.clone(),
+        }
+    }
+}
+impl<T> std::fmt::Debug for AnyOf<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AnyOf").field(&self.filters).finish()
+    }
+}
+
+/// Matches values which match all of its filters, an empty `AllOf`
+/// matches everything.
+///
+/// This is the same logic as a [FilterSet], but can be nested within
+/// other filters.
+pub struct AllOf<T> {
+    filters: Vec<Box<dyn Filter<T>>>,
+}
+impl<T> AllOf<T> {
+    /// Adds another filter to match against.
+    pub fn add_filter<F: Filter<T> + 'static>(mut self, filter: F) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+}
+impl<T> Filter<T> for AllOf<T> {
+    fn matches(&self, val: &T) -> bool {
+        self.filters.iter().all(|filter| filter.matches(val))
+    }
+}
+impl<T> Default for AllOf<T> {
+    fn default() -> Self {
+        Self {
+            filters: Vec::new(),
+        }
+    }
+}
+/// Manually implemented for the same reason as [FilterSet]'s [Clone].
+impl<T> Clone for AllOf<T> {
+    fn clone(&self) -> Self {
+        Self {
+            filters: self.filters.clone(),
+        }
+    }
+}
+impl<T> std::fmt::Debug for AllOf<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AllOf").field(&self.filters).finish()
+    }
+}
+
+/// Matches values using the given closure, for one-off predicates that
+/// don't warrant their own [Filter].
+///
+/// ```
+/// # use stork::filters::FnFilter;
+/// # use stork::FilterSet;
+/// let filters = FilterSet::default()
+///     .add_filter(FnFilter::new(|val: &String| val.len() < 10));
+/// ```
+pub struct FnFilter<T>(Arc<dyn Fn(&T) -> bool + Send + Sync>);
+impl<T> FnFilter<T> {
+    pub fn new<F: Fn(&T) -> bool + Send + Sync + 'static>(f: F) -> Self {
+        Self(Arc::new(f))
+    }
+}
+impl<T> Filter<T> for FnFilter<T> {
+    fn matches(&self, val: &T) -> bool {
+        (self.0)(val)
+    }
+}
+impl<T> Clone for FnFilter<T> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+impl<T> std::fmt::Debug for FnFilter<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("FnFilter(..)")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum FilterType {
     StartsWith,
     EndsWith,
     Contains,
-    Equals
+    Equals,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn below(max: u32) -> FnFilter<u32> {
+        FnFilter::new(move |val: &u32| *val < max)
+    }
+
+    fn even() -> FnFilter<u32> {
+        FnFilter::new(|val: &u32| val.is_multiple_of(2))
+    }
+
+    #[test]
+    fn not() {
+        assert!(Not(even()).matches(&3));
+        assert!(!Not(even()).matches(&4));
+        assert!(Not(Not(even())).matches(&4));
+    }
+
+    #[test]
+    fn any_of() {
+        let filter = AnyOf::default().add_filter(below(3)).add_filter(even());
+
+        assert!(filter.matches(&1));
+        assert!(filter.matches(&4));
+        assert!(!filter.matches(&5));
+    }
+
+    #[test]
+    fn all_of() {
+        let filter = AllOf::default().add_filter(below(5)).add_filter(even());
+
+        assert!(filter.matches(&2));
+        assert!(!filter.matches(&3));
+        assert!(!filter.matches(&6));
+    }
+
+    #[test]
+    fn empty_combinators() {
+        assert!(!AnyOf::<u32>::default().matches(&1));
+        assert!(AllOf::<u32>::default().matches(&1));
+        assert!(FilterSet::<u32>::default().matches(&1));
+    }
+
+    #[test]
+    fn nesting() {
+        // odd numbers below 10, or anything from 100 up
+        let filter = AnyOf::default()
+            .add_filter(
+                AllOf::default()
+                    .add_filter(below(10))
+                    .add_filter(Not(even())),
+            )
+            .add_filter(Not(below(100)));
+
+        assert!(filter.matches(&3));
+        assert!(!filter.matches(&4));
+        assert!(!filter.matches(&11));
+        assert!(filter.matches(&100));
+
+        let filters = FilterSet::default().add_filter(Not(filter));
+        assert!(filters.matches(&4));
+        assert!(!filters.matches(&3));
+    }
+
+    #[test]
+    fn clones_are_independent() {
+        let filter = AnyOf::default().add_filter(below(3));
+        let extended = filter.clone().add_filter(even());
+
+        assert!(!filter.matches(&4));
+        assert!(extended.matches(&4));
+    }
 }
@@ -8,29 +8,37 @@
 //! a look through the [Storkable] struct's documentation for your
 //! entry into the world of storking.
 //!
+//! If you'd rather not drive the traversal yourself, hand your root
+//! [Storkable] to a [Crawler] which will recursively exec everything
+//! it finds using a breadth-first, depth-first or best-first strategy.
+//!
+//! So long as your [StorkClient] returns a [ClientStream], the streams
+//! returned by [Storkable::exec] and [Crawler::crawl] are [Send] and can
+//! be spawned onto a multi-threaded executor, allowing separate
+//! branches of a crawl to be storked in parallel.
+//!
 //! *Note: you're probably not looking for this library on its own but
 //! a protocol implementation of it. See below for some first-party
 //! implementations:*
 //! - [stork_http](../../../stork_http/)
-#![recursion_limit = "256"]
-
-#[macro_use]
-extern crate failure_derive;
+#![recursion_limit = "512"]
 
+pub mod crawler;
 pub mod errors;
 pub mod filters;
+pub mod seen;
 
-pub use errors::StorkError;
+pub use crawler::Crawler;
+pub use errors::{BoxError, StorkError};
 pub use filters::FilterSet;
+pub use seen::SeenStore;
 
-use async_stream::try_stream;
+use async_stream::stream;
 use futures::prelude::*;
 
 use std::pin::Pin;
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 
-use failure::Error;
-use failure::ResultExt;
 use std::hash::{Hash, Hasher};
 
 /// A [Storkable] represents a "thing" which is traversable ("storkable").
@@ -48,13 +56,17 @@ use std::hash::{Hash, Hasher};
 /// `T` and `C: StorkClient<T>`. The `StorkClient` implementation will
 /// be called with a value of `T`, and is expected to return all the
 /// values of `T` that can be found on the given `T`.
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Storkable<T: Unpin + PartialEq + Hash, C: StorkClient<T>> {
     value: T,
     filters: FilterSet<T>,
     client: Arc<C>,
     parent: Option<Arc<Storkable<T, C>>>,
-    seen: Arc<RwLock<Vec<u64>>>,
+    depth: usize,
+    dedup: DedupPolicy,
+    error_policy: ErrorPolicy,
+    seen: Arc<dyn SeenStore>,
+    page: Option<Arc<C::Page>>,
 }
 
 impl<'a, T: Unpin + PartialEq + Hash + 'a, C: StorkClient<T> + 'a> Storkable<T, C> {
@@ -66,7 +78,11 @@ impl<'a, T: Unpin + PartialEq + Hash + 'a, C: StorkClient<T> + 'a> Storkable<T,
             filters: FilterSet::default(),
             client: Arc::new(C::default()),
             parent: None,
-            seen: Arc::new(RwLock::new(Vec::new())),
+            depth: 0,
+            dedup: DedupPolicy::default(),
+            error_policy: ErrorPolicy::default(),
+            seen: Arc::new(seen::HashSetStore::default()),
+            page: None,
         }
     }
 
@@ -77,6 +93,35 @@ impl<'a, T: Unpin + PartialEq + Hash + 'a, C: StorkClient<T> + 'a> Storkable<T,
         self
     }
 
+    /// Sets how this [Storkable] and any children found after executing
+    /// it should deduplicate the values they yield, see [DedupPolicy].
+    ///
+    /// This should generally be set on the root [Storkable] before
+    /// storking begins.
+    pub fn with_dedup(mut self, dedup: DedupPolicy) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Sets what this [Storkable] and any children found after executing
+    /// it should do when storking runs into an error, see [ErrorPolicy].
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    /// Replaces the [SeenStore] used to keep track of the values this
+    /// [Storkable] has yielded. When using [DedupPolicy::PerCrawl] the
+    /// store will be shared with all children found after executing
+    /// this one, otherwise children get their own empty store of the
+    /// same kind when they're storked, see [SeenStore::fresh].
+    ///
+    /// See the [seen] module for the stores shipped with stork.
+    pub fn with_seen_store<S: SeenStore + 'static>(mut self, store: S) -> Self {
+        self.seen = Arc::new(store);
+        self
+    }
+
     /// Replaces the default [StorkClient] with a new one accepting
     /// and returning the same type for this [Storkable].
     pub fn with_client(mut self, client: C) -> Self {
@@ -100,6 +145,20 @@ impl<'a, T: Unpin + PartialEq + Hash + 'a, C: StorkClient<T> + 'a> Storkable<T,
         self.parent.as_ref().map(Arc::as_ref)
     }
 
+    /// Get the number of [Storkable]s between this one and the root
+    /// [Storkable] it was found from, the root itself has a depth of 0.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Get what the [StorkClient] found out about this [Storkable]
+    /// whilst storking it, or `None` if it hasn't been storked. This is
+    /// set on the [Storkable] yielded as [Storked::Fetched] by
+    /// [Storkable::exec], and on the parent of each of its children.
+    pub fn page(&self) -> Option<&C::Page> {
+        self.page.as_deref()
+    }
+
     /// Checks if this Storkable, or any parent Storkables have the same
     /// value as the one given.
     fn check_parent_is(&self, value: &T) -> bool {
@@ -119,60 +178,238 @@ impl<'a, T: Unpin + PartialEq + Hash + 'a, C: StorkClient<T> + 'a> Storkable<T,
     /// Checks if this Storkable has seen this `value` before. If it
     /// hasn't, this method will return false but any subsequent calls
     /// with the same value will return true.
-    fn check_has_seen(&self, value: &T) -> bool {
+    fn check_has_seen(&self, value: &T) -> Result<bool, StorkError<C::Error>> {
         let mut hasher = twox_hash::XxHash64::default();
         value.hash(&mut hasher);
         let hash = hasher.finish();
 
-        return if self.seen.read().unwrap().contains(&hash) {
-            true
-        } else {
-            self.seen.write().unwrap().push(hash);
-            false
-        };
+        self.seen
+            .check_and_insert(hash)
+            .map_err(StorkError::SeenStoreError)
     }
 
     /// Start storking this [Storkable].
     ///
     /// Finds all the followable links on this [Storkable] and returns
     /// a stream of more [Storkable]s with the same filters and the
-    /// `parent` set to a reference of the current [Storkable].
-    pub fn exec<'b>(self) -> impl futures::Stream<Item = Result<Storkable<T, C>, Error>> + 'a {
-        let this = Arc::new(self);
-
-        try_stream! {
-            let mut children = this.client.run(this.val());
-
-            while let Some(child) = children.next().await {
-                let child = child.context(StorkError::ClientError)?;
+    /// `parent` set to a reference of the current [Storkable], see
+    /// [Storked]. Once the client has said what it found out about this
+    /// [Storkable] it's yielded first as a [Storked::Fetched], with its
+    /// [Storkable::page] set.
+    ///
+    /// Errors from the client, ie. a single link on the page that
+    /// couldn't be understood, are handled according to the
+    /// [ErrorPolicy] set on this [Storkable]. By default they're
+    /// yielded and storking carries on with the rest of the children.
+    ///
+    /// The returned stream is [Send] if `T`, `C` and the stream returned
+    /// by `C`'s [StorkClient::run] are.
+    pub fn exec(
+        self,
+    ) -> impl futures::Stream<Item = Result<Storked<T, C>, StorkError<C::Error>>> + 'a
+    where
+        T: Clone,
+    {
+        let mut this = self;
 
-                if !this.filters.matches(&child) {
-                    continue;
+        stream! {
+            // children start off holding their parent's store so that
+            // only the ones which actually get storked pay for one of
+            // their own
+            if this.dedup == DedupPolicy::PerParent && this.parent.is_some() {
+                match this.seen.fresh() {
+                    Ok(seen) => this.seen = seen,
+                    Err(e) => {
+                        yield Err(StorkError::SeenStoreError(e));
+                        return;
+                    }
                 }
+            }
 
-                // ensure we haven't returned this link before from this
-                // Storkable
-                if this.check_has_seen(&child) {
-                    continue;
+            // mark the root as seen so it doesn't get yielded back as a
+            // child of one of its descendants and storked all over again
+            if this.dedup == DedupPolicy::PerCrawl && this.parent.is_none() {
+                if let Err(e) = this.check_has_seen(this.val()) {
+                    yield Err(e);
+                    return;
                 }
+            }
 
-                // ensure we're not going to cause a recursive loop by
-                // checking that the page we're about to yield isn't a
-                // parent of it
-                if this.check_parent_is(&child) {
-                    continue;
+            let children = this.client.run(this.val());
+            futures::pin_mut!(children);
+
+            // the page comes before any of the children, and needs to be
+            // on us before they're created so they can get at it through
+            // their parent
+            let first = match children.next().await {
+                Some(Ok(Found::Page(page))) => {
+                    this.page = Some(Arc::new(page));
+                    None
                 }
+                first => first,
+            };
+
+            let this = Arc::new(this);
+            if this.page.is_some() {
+                yield Ok(Storked::Fetched(Storkable::clone(&this)));
+            }
+
+            let children = stream::iter(first).chain(children);
+            futures::pin_mut!(children);
 
-                yield Storkable {
-                    value: child,
-                    client: Arc::clone(&this.client),
-                    filters: this.filters.clone(),
-                    parent: Some(Arc::clone(&this)),
-                    seen: Arc::new(RwLock::new(Vec::new())),
+            while let Some(child) = children.next().await {
+                let child = match child {
+                    Ok(Found::Child(child)) => Self::check_child(&this, child),
+                    // there's nothing more to be said about the page once
+                    // the client has moved on to its children
+                    Ok(Found::Page(_)) => Ok(None),
+                    Err(e) => Err(StorkError::ClientError(e)),
                 };
+
+                match child {
+                    Ok(Some(child)) => yield Ok(child),
+                    Ok(None) => {}
+                    Err(e) => match this.error_policy {
+                        ErrorPolicy::Skip => {}
+                        ErrorPolicy::YieldAndContinue => yield Err(e),
+                        ErrorPolicy::Abort => {
+                            yield Err(e);
+                            return;
+                        }
+                    },
+                }
             }
         }
     }
+
+    /// Wraps `value`, found whilst storking `parent`, in a [Storkable]
+    /// inheriting the parent's configuration.
+    fn child_of(parent: &Arc<Self>, value: T) -> Self {
+        Storkable {
+            value,
+            client: Arc::clone(&parent.client),
+            filters: parent.filters.clone(),
+            parent: Some(Arc::clone(parent)),
+            depth: parent.depth + 1,
+            dedup: parent.dedup,
+            error_policy: parent.error_policy,
+            seen: Arc::clone(&parent.seen),
+            page: None,
+        }
+    }
+
+    /// Checks if `child`, found whilst storking `parent`, should be
+    /// yielded, returning it wrapped in a [Storkable] if so.
+    fn check_child(
+        parent: &Arc<Self>,
+        child: T,
+    ) -> Result<Option<Storked<T, C>>, StorkError<C::Error>> {
+        if !parent.filters.matches(&child) {
+            return Ok(None);
+        }
+
+        // ensure we haven't returned this link before from this
+        // Storkable (or from anywhere in the crawl, depending on the
+        // dedup policy), and that we're not going to cause a recursive
+        // loop by yielding one of our own parents
+        let seen = (parent.dedup != DedupPolicy::None && parent.check_has_seen(&child)?)
+            || parent.check_parent_is(&child);
+
+        let child = Self::child_of(parent, child);
+        if seen {
+            Ok(Some(Storked::Seen(child)))
+        } else {
+            Ok(Some(Storked::Child(child)))
+        }
+    }
+}
+
+/// We need to manually implement [Clone] for this struct because
+/// otherwise it'd require `C` to be [Clone] too, which is unnecessary
+/// since the client is shared between all [Storkable]s via an [Arc].
+impl<T: Unpin + PartialEq + Hash + Clone, C: StorkClient<T>> Clone for Storkable<T, C> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            filters: self.filters.clone(),
+            client: Arc::clone(&self.client),
+            parent: self.parent.clone(),
+            depth: self.depth,
+            dedup: self.dedup,
+            error_policy: self.error_policy,
+            seen: Arc::clone(&self.seen),
+            page: self.page.clone(),
+        }
+    }
+}
+
+/// Something found whilst storking a [Storkable], see
+/// [Storkable::exec] and [Crawler::crawl].
+#[derive(Debug)]
+pub enum Storked<T: Unpin + PartialEq + Hash, C: StorkClient<T>> {
+    /// The [Storkable] that was storked, with [Storkable::page] set to
+    /// whatever the client found out about it. Yielded before any of its
+    /// children.
+    Fetched(Storkable<T, C>),
+    /// A child found whilst storking, which can be storked in turn.
+    Child(Storkable<T, C>),
+    /// A child which had already been found, either elsewhere (depending
+    /// on the [DedupPolicy]) or as one of its own parents, so isn't worth
+    /// storking again. Useful for drawing a graph of which pages link to
+    /// which.
+    Seen(Storkable<T, C>),
+}
+
+impl<T: Unpin + PartialEq + Hash, C: StorkClient<T>> Storked<T, C> {
+    /// Get the [Storkable] that was found, whichever way it was found.
+    pub fn storkable(&self) -> &Storkable<T, C> {
+        match self {
+            Storked::Fetched(storkable) | Storked::Child(storkable) | Storked::Seen(storkable) => {
+                storkable
+            }
+        }
+    }
+
+    /// Takes the child found, if this is a [Storked::Child].
+    pub fn into_child(self) -> Option<Storkable<T, C>> {
+        match self {
+            Storked::Child(child) => Some(child),
+            _ => None,
+        }
+    }
+}
+
+/// Decides the scope in which a [Storkable] and its children will
+/// deduplicate the values they yield.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DedupPolicy {
+    /// Each [Storkable] yields a given value at most once, but the
+    /// same value may be yielded by any number of different
+    /// [Storkable]s. This is the default.
+    #[default]
+    PerParent,
+    /// A value is yielded at most once across the whole tree of
+    /// [Storkable]s descending from the root the policy was set on, so
+    /// nothing will ever be storked twice in the same crawl.
+    PerCrawl,
+    /// Values are never deduplicated, only a [Storkable] being yielded
+    /// as a child of itself is prevented.
+    None,
+}
+
+/// Decides what a [Storkable] and its children do when storking runs
+/// into an error, whether that's the client failing to understand one
+/// of the children it found or failing altogether.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Errors are silently dropped and storking carries on.
+    Skip,
+    /// Errors are yielded in place of the child that caused them and
+    /// storking carries on. This is the default.
+    #[default]
+    YieldAndContinue,
+    /// The first error is yielded and storking stops there.
+    Abort,
 }
 
 /// A [StorkClient] is an underlying implementation of a storker. When a
@@ -185,7 +422,290 @@ impl<'a, T: Unpin + PartialEq + Hash + 'a, C: StorkClient<T> + 'a> Storkable<T,
 ///
 /// [StorkClient]s may be used across threads and *must* be thread-safe.
 pub trait StorkClient<T>: Default {
+    /// The error yielded by the stream returned from [StorkClient::run],
+    /// which will be handed back wrapped in a
+    /// [StorkError::ClientError].
+    type Error: std::error::Error + Send + Sync + Unpin + 'static;
+
+    /// What the client finds out about a `T` whilst storking it, such as
+    /// the response a web page was served with. This is kept on the
+    /// [Storkable] that was storked, see [Storkable::page]. Clients with
+    /// nothing to say can use `()`.
+    type Page: std::fmt::Debug + 'static;
+
+    /// The stream returned from [StorkClient::run], this should almost
+    /// always be a [ClientStream] so storking can happen across threads.
+    /// Clients that need to hold on to something that can't be sent
+    /// between threads can use a [LocalClientStream] instead, at the
+    /// cost of their [Storkable]s only being storkable on one thread.
+    type Stream: futures::Stream<Item = Result<Found<T, Self::Page>, Self::Error>> + 'static;
+
     /// Makes a call to `T` and returns the child `T`s it can find on the
-    /// page.
-    fn run(&self, src: &T) -> Pin<Box<dyn futures::Stream<Item = Result<T, Error>>>>;
+    /// page, preceded by the [Found::Page] describing the page itself.
+    ///
+    /// An error understanding a single child should be yielded in its
+    /// place, carrying on with the rest of them, so the [Storkable]'s
+    /// [ErrorPolicy] can decide what to do with it.
+    fn run(&self, src: &T) -> Self::Stream;
+}
+
+/// Something a [StorkClient] found whilst storking a `T`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Found<T, P> {
+    /// What was found out about the `T` being storked. This should be
+    /// yielded at most once, before any children, and not at all if the
+    /// `T` couldn't be storked.
+    Page(P),
+    /// A child `T` found on the page.
+    Child(T),
+}
+
+/// A boxed stream of `T`s (or `E`s, for anything that went wrong)
+/// returned from a [StorkClient] which can be sent between threads.
+pub type ClientStream<T, E> = Pin<Box<dyn futures::Stream<Item = Result<T, E>> + Send>>;
+
+/// A boxed stream of `T`s (or `E`s, for anything that went wrong)
+/// returned from a [StorkClient] which can't be sent between threads.
+pub type LocalClientStream<T, E> = Pin<Box<dyn futures::Stream<Item = Result<T, E>>>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Links every number below 8 to its two children in a binary tree,
+    /// and every number to 1. The page says how many links were on it.
+    #[derive(Debug, Default)]
+    pub(crate) struct Tree;
+    impl StorkClient<u32> for Tree {
+        type Error = std::io::Error;
+        type Page = usize;
+        type Stream = ClientStream<Found<u32, usize>, std::io::Error>;
+
+        fn run(&self, src: &u32) -> Self::Stream {
+            let mut children = vec![1];
+            if *src < 8 {
+                children.extend(&[src * 2, src * 2 + 1, src * 2]);
+            }
+
+            let page = Found::Page(children.len());
+            let found = std::iter::once(page).chain(children.into_iter().map(Found::Child));
+            Box::pin(stream::iter(found.map(Ok)))
+        }
+    }
+
+    /// Finds 1, fails to understand a child, then finds 2. Storking 0
+    /// fails altogether.
+    #[derive(Debug, Default)]
+    struct Flaky;
+    impl StorkClient<u32> for Flaky {
+        type Error = std::io::Error;
+        type Page = ();
+        type Stream = ClientStream<Found<u32, ()>, std::io::Error>;
+
+        fn run(&self, src: &u32) -> Self::Stream {
+            let error = || std::io::Error::other("flaky");
+
+            let found = if *src == 0 {
+                vec![Err(error())]
+            } else {
+                vec![
+                    Ok(Found::Page(())),
+                    Ok(Found::Child(1)),
+                    Err(error()),
+                    Ok(Found::Child(2)),
+                ]
+            };
+            Box::pin(stream::iter(found))
+        }
+    }
+
+    /// [SeenStore] counting how many stores have been created from it.
+    #[derive(Debug, Default)]
+    struct CountingStore {
+        inner: seen::HashSetStore,
+        created: Arc<AtomicUsize>,
+    }
+    impl SeenStore for CountingStore {
+        fn check_and_insert(&self, hash: u64) -> Result<bool, BoxError> {
+            self.inner.check_and_insert(hash)
+        }
+
+        fn fresh(&self) -> Result<Arc<dyn SeenStore>, BoxError> {
+            self.created.fetch_add(1, Ordering::SeqCst);
+
+            Ok(Arc::new(Self {
+                inner: Default::default(),
+                created: Arc::clone(&self.created),
+            }))
+        }
+    }
+
+    fn values(storkables: &[Storkable<u32, Tree>]) -> Vec<u32> {
+        storkables.iter().map(|s| *s.val()).collect()
+    }
+
+    async fn exec(storkable: Storkable<u32, Tree>) -> Vec<Storkable<u32, Tree>> {
+        storkable
+            .exec()
+            .filter_map(|found| future::ready(found.unwrap().into_child()))
+            .collect::<Vec<_>>()
+            .await
+    }
+
+    /// Storks `value` with the given [ErrorPolicy], returning the values
+    /// of the children found with `None` in place of each error.
+    async fn exec_flaky(value: u32, error_policy: ErrorPolicy) -> Vec<Option<u32>> {
+        Storkable::<_, Flaky>::new(value)
+            .with_dedup(DedupPolicy::None)
+            .with_error_policy(error_policy)
+            .exec()
+            .filter_map(|found| {
+                future::ready(match found {
+                    Ok(Storked::Fetched(_)) => None,
+                    Ok(found) => Some(Some(*found.storkable().val())),
+                    Err(StorkError::ClientError(_)) => Some(None),
+                    Err(e) => panic!("unexpected error {:?}", e),
+                })
+            })
+            .collect::<Vec<_>>()
+            .await
+    }
+
+    #[tokio::test]
+    async fn per_parent_dedup() {
+        let children = exec(Storkable::new(1)).await;
+        assert_eq!(values(&children), vec![2, 3]);
+
+        // 1 is 2's parent, so isn't yielded again
+        let grandchildren = exec(children[0].clone()).await;
+        assert_eq!(values(&grandchildren), vec![4, 5]);
+        assert_eq!(grandchildren[0].depth(), 2);
+    }
+
+    #[tokio::test]
+    async fn per_crawl_dedup() {
+        let root = Storkable::new(2).with_dedup(DedupPolicy::PerCrawl);
+        let children = exec(root).await;
+        assert_eq!(values(&children), vec![1, 4, 5]);
+
+        // 2 (the root) and 4 and 5 have all been yielded already
+        let grandchildren = exec(children[0].clone()).await;
+        assert_eq!(values(&grandchildren), vec![3]);
+    }
+
+    #[tokio::test]
+    async fn page_is_kept_on_the_storked_storkable() {
+        let found = Storkable::<_, Tree>::new(2)
+            .exec()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await;
+
+        // the storkable that was storked comes first, with its page
+        match &found[0] {
+            Storked::Fetched(fetched) => {
+                assert_eq!(*fetched.val(), 2);
+                assert_eq!(fetched.page(), Some(&4));
+            }
+            other => panic!("expected the storked storkable, got {:?}", other),
+        }
+
+        // followed by its children, which can get at it through their
+        // parent but don't have a page of their own yet
+        let children = found[1..]
+            .iter()
+            .filter_map(|found| match found {
+                Storked::Child(child) => Some(child),
+                Storked::Seen(_) => None,
+                other => panic!("expected a child, got {:?}", other),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            children
+                .iter()
+                .map(|child| *child.val())
+                .collect::<Vec<_>>(),
+            vec![1, 4, 5]
+        );
+        assert!(children.iter().all(|child| child.page().is_none()));
+        assert!(children
+            .iter()
+            .all(|child| child.parent().unwrap().page() == Some(&4)));
+    }
+
+    #[tokio::test]
+    async fn seen_children() {
+        let children = exec(Storkable::new(1)).await;
+
+        // 2 links back to its parent 1 and to 4 twice
+        let found = children[0]
+            .clone()
+            .exec()
+            .map(Result::unwrap)
+            .filter_map(|found| {
+                future::ready(match found {
+                    Storked::Seen(seen) => Some(*seen.val()),
+                    _ => None,
+                })
+            })
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(found, vec![1, 4]);
+    }
+
+    #[tokio::test]
+    async fn seen_store_reaches_children() {
+        let created = Arc::new(AtomicUsize::new(0));
+        let root = Storkable::new(1).with_seen_store(CountingStore {
+            inner: Default::default(),
+            created: Arc::clone(&created),
+        });
+
+        // the root uses the store it was given, and children only get
+        // their own once they're storked
+        let children = exec(root).await;
+        assert_eq!(created.load(Ordering::SeqCst), 0);
+
+        // the children's stores have to have come from ours too for
+        // their own children to get one
+        let grandchildren = exec(children[0].clone()).await;
+        assert_eq!(created.load(Ordering::SeqCst), 1);
+        exec(grandchildren[0].clone()).await;
+        assert_eq!(created.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn skip_errors() {
+        let found = exec_flaky(3, ErrorPolicy::Skip).await;
+        assert_eq!(found, vec![Some(1), Some(2)]);
+    }
+
+    #[tokio::test]
+    async fn yield_errors_and_continue() {
+        let found = exec_flaky(3, ErrorPolicy::YieldAndContinue).await;
+        assert_eq!(found, vec![Some(1), None, Some(2)]);
+    }
+
+    #[tokio::test]
+    async fn abort_on_error() {
+        let found = exec_flaky(3, ErrorPolicy::Abort).await;
+        assert_eq!(found, vec![Some(1), None]);
+    }
+
+    #[tokio::test]
+    async fn failed_fetch() {
+        let found = Storkable::<_, Flaky>::new(0)
+            .exec()
+            .collect::<Vec<_>>()
+            .await;
+
+        // there's no page, so nothing was fetched, just the error
+        assert_eq!(found.len(), 1);
+        assert!(matches!(found[0], Err(StorkError::ClientError(_))));
+
+        let found = exec_flaky(0, ErrorPolicy::Skip).await;
+        assert!(found.is_empty());
+    }
 }
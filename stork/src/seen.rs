@@ -0,0 +1,332 @@
+//! Backends for keeping track of which values a
+//! [Storkable](crate::Storkable) has already yielded.
+//!
+//! Values are hashed down to a `u64` before they reach a [SeenStore]
+//! so stores never need to know anything about the type being storked.
+//!
+//! - [HashSetStore] is an exact in-memory store, and the default.
+//! - [BloomStore] trades a configurable false-positive rate for a far
+//!   smaller memory footprint on very large crawls.
+//! - [SledStore] (behind the `sled` feature) keeps the set on disk for
+//!   crawls that outgrow memory altogether.
+
+use crate::BoxError;
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+/// A set of hashes the [Storkable](crate::Storkable)s in a crawl have
+/// already yielded. A custom store can be attached to a root
+/// [Storkable](crate::Storkable) using
+/// [Storkable::with_seen_store](crate::Storkable::with_seen_store).
+///
+/// With [DedupPolicy::PerCrawl](crate::DedupPolicy::PerCrawl) a
+/// [SeenStore] is shared between every [Storkable](crate::Storkable) in
+/// a crawl, otherwise each [Storkable](crate::Storkable) gets its own
+/// created using [SeenStore::fresh]. Either way they *must* be
+/// thread-safe.
+pub trait SeenStore: std::fmt::Debug + Send + Sync {
+    /// Records the given `hash` as seen, returning whether or not it
+    /// had been seen before this call.
+    ///
+    /// Implementations must do this atomically so two callers racing
+    /// to insert the same hash can't both be told it's new.
+    fn check_and_insert(&self, hash: u64) -> Result<bool, BoxError>;
+
+    /// Creates a new, empty store configured the same as this one, for
+    /// a child [Storkable](crate::Storkable) which deduplicates the
+    /// values it yields separately from its parent.
+    fn fresh(&self) -> Result<Arc<dyn SeenStore>, BoxError>;
+}
+
+/// Exact in-memory [SeenStore] backed by a [HashSet].
+#[derive(Debug, Default)]
+pub struct HashSetStore(Mutex<HashSet<u64>>);
+impl SeenStore for HashSetStore {
+    fn check_and_insert(&self, hash: u64) -> Result<bool, BoxError> {
+        Ok(!self.0.lock().unwrap().insert(hash))
+    }
+
+    fn fresh(&self) -> Result<Arc<dyn SeenStore>, BoxError> {
+        Ok(Arc::new(Self::default()))
+    }
+}
+
+/// Probabilistic [SeenStore] backed by a scalable Bloom filter.
+///
+/// Memory usage grows with the number of values inserted, whilst the
+/// chance of a value that has *not* been seen being reported as seen
+/// stays below the configured false-positive rate. Values that have
+/// been seen are always reported as such.
+#[derive(Debug)]
+pub struct BloomStore(Mutex<ScalableBloom>);
+impl BloomStore {
+    /// Creates a new [BloomStore] which will initially size itself for
+    /// `capacity` values and aims to keep its overall false-positive
+    /// rate below `false_positive_rate`.
+    pub fn new(capacity: usize, false_positive_rate: f64) -> Self {
+        assert!(
+            false_positive_rate > 0.0 && false_positive_rate < 1.0,
+            "false positive rate must be between 0 and 1"
+        );
+
+        Self(Mutex::new(ScalableBloom {
+            filters: Vec::new(),
+            initial_capacity: capacity.max(1),
+            // each filter gets half the error rate of the one before it,
+            // so the sum of all their rates converges on the target rate
+            initial_error_rate: false_positive_rate * (1.0 - TIGHTENING_RATIO),
+        }))
+    }
+}
+impl Default for BloomStore {
+    /// Creates a [BloomStore] sized for 100,000 values with a
+    /// false-positive rate of 0.1%.
+    fn default() -> Self {
+        Self::new(100_000, 0.001)
+    }
+}
+impl SeenStore for BloomStore {
+    fn check_and_insert(&self, hash: u64) -> Result<bool, BoxError> {
+        Ok(self.0.lock().unwrap().check_and_insert(hash))
+    }
+
+    fn fresh(&self) -> Result<Arc<dyn SeenStore>, BoxError> {
+        let bloom = self.0.lock().unwrap();
+
+        Ok(Arc::new(Self(Mutex::new(ScalableBloom {
+            filters: Vec::new(),
+            initial_capacity: bloom.initial_capacity,
+            initial_error_rate: bloom.initial_error_rate,
+        }))))
+    }
+}
+
+/// Factor each new filter's error rate is multiplied by when a
+/// [ScalableBloom] grows.
+const TIGHTENING_RATIO: f64 = 0.5;
+
+#[derive(Debug)]
+struct ScalableBloom {
+    filters: Vec<Bloom>,
+    initial_capacity: usize,
+    initial_error_rate: f64,
+}
+impl ScalableBloom {
+    fn check_and_insert(&mut self, hash: u64) -> bool {
+        if self.filters.iter().any(|filter| filter.contains(hash)) {
+            return true;
+        }
+
+        let needs_growth = self
+            .filters
+            .last()
+            .is_none_or(|filter| filter.len >= filter.capacity);
+        if needs_growth {
+            // each new filter is twice the size of the one before it
+            let generation = self.filters.len() as u32;
+            self.filters.push(Bloom::new(
+                self.initial_capacity
+                    .saturating_mul(2usize.saturating_pow(generation)),
+                self.initial_error_rate * TIGHTENING_RATIO.powi(generation as i32),
+            ));
+        }
+
+        // unwrap can't panic here because we pushed a filter above if
+        // there wasn't one already
+        self.filters.last_mut().unwrap().insert(hash);
+
+        false
+    }
+}
+
+#[derive(Debug)]
+struct Bloom {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+    capacity: usize,
+    len: usize,
+}
+impl Bloom {
+    fn new(capacity: usize, error_rate: f64) -> Self {
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-(capacity as f64) * error_rate.ln() / (ln2 * ln2)).ceil() as u64;
+        let num_bits = num_bits.max(64);
+        let num_hashes = ((num_bits as f64 / capacity as f64) * ln2).round().max(1.0) as u32;
+
+        Self {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+            capacity,
+            len: 0,
+        }
+    }
+
+    /// Derives the bit indices for `hash` using double hashing, the
+    /// second hash being a remix of the first.
+    fn indices(&self, hash: u64) -> impl Iterator<Item = u64> {
+        let num_bits = self.num_bits;
+        let h1 = hash;
+        let h2 = splitmix64(hash) | 1;
+
+        (0..u64::from(self.num_hashes)).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+    }
+
+    fn contains(&self, hash: u64) -> bool {
+        self.indices(hash)
+            .all(|i| self.bits[(i / 64) as usize] & (1 << (i % 64)) != 0)
+    }
+
+    fn insert(&mut self, hash: u64) {
+        for i in self.indices(hash).collect::<Vec<_>>() {
+            self.bits[(i / 64) as usize] |= 1 << (i % 64);
+        }
+
+        self.len += 1;
+    }
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// Exact on-disk [SeenStore] backed by a [sled] database, for crawls
+/// with more values than will comfortably fit in memory.
+///
+/// Stores created using [SeenStore::fresh] live in their own tree in the
+/// same database, which is dropped along with the store.
+#[cfg(feature = "sled")]
+#[derive(Debug)]
+pub struct SledStore {
+    db: sled::Db,
+    /// The tree this store's hashes are kept in, if it isn't the
+    /// database's default tree.
+    tree: Option<sled::Tree>,
+}
+#[cfg(feature = "sled")]
+impl SledStore {
+    /// Opens (or creates) a store at the given path. Hashes already in
+    /// an existing store will be reported as seen, allowing a crawl to
+    /// pick up where a previous one left off.
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Self, sled::Error> {
+        Ok(Self {
+            db: sled::open(path)?,
+            tree: None,
+        })
+    }
+
+    /// Creates a store in a temporary location which is removed once
+    /// the store is dropped.
+    pub fn temporary() -> Result<Self, sled::Error> {
+        Ok(Self {
+            db: sled::Config::new().temporary(true).open()?,
+            tree: None,
+        })
+    }
+}
+#[cfg(feature = "sled")]
+impl SeenStore for SledStore {
+    fn check_and_insert(&self, hash: u64) -> Result<bool, BoxError> {
+        let tree = self.tree.as_ref().unwrap_or(&self.db);
+        Ok(tree.insert(hash.to_be_bytes(), &[])?.is_some())
+    }
+
+    fn fresh(&self) -> Result<Arc<dyn SeenStore>, BoxError> {
+        let name = format!("stork-seen-{}", self.db.generate_id()?);
+
+        Ok(Arc::new(Self {
+            db: self.db.clone(),
+            tree: Some(self.db.open_tree(name)?),
+        }))
+    }
+}
+#[cfg(feature = "sled")]
+impl Drop for SledStore {
+    fn drop(&mut self) {
+        if let Some(tree) = self.tree.take() {
+            // nothing we can do about it if this fails, we'd only be
+            // leaving behind some hashes nobody will look at
+            let _ = self.db.drop_tree(tree.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_set_store() {
+        let store = HashSetStore::default();
+
+        assert!(!store.check_and_insert(1).unwrap());
+        assert!(store.check_and_insert(1).unwrap());
+        assert!(!store.check_and_insert(2).unwrap());
+        assert!(!store.fresh().unwrap().check_and_insert(1).unwrap());
+    }
+
+    #[test]
+    fn bloom_store_has_no_false_negatives() {
+        // small enough that the filter has to grow several times
+        let store = BloomStore::new(100, 0.01);
+
+        assert!(!store.check_and_insert(splitmix64(0)).unwrap());
+        for hash in 1..10_000u64 {
+            store.check_and_insert(splitmix64(hash)).unwrap();
+        }
+        for hash in 0..10_000u64 {
+            assert!(store.check_and_insert(splitmix64(hash)).unwrap());
+        }
+    }
+
+    #[test]
+    fn bloom_store_false_positive_rate() {
+        let store = BloomStore::new(1_000, 0.01);
+        for hash in 0..10_000u64 {
+            store.check_and_insert(splitmix64(hash)).unwrap();
+        }
+
+        let false_positives = (10_000..110_000u64)
+            .filter(|&hash| store.check_and_insert(splitmix64(hash)).unwrap())
+            .count();
+
+        // a little slack over 1% for randomness
+        assert!(
+            false_positives < 1_500,
+            "{} false positives",
+            false_positives
+        );
+    }
+
+    #[test]
+    fn bloom_store_fresh_is_empty() {
+        let store = BloomStore::new(10, 0.01);
+        store.check_and_insert(1).unwrap();
+
+        let fresh = store.fresh().unwrap();
+        assert!(!fresh.check_and_insert(1).unwrap());
+        assert!(fresh.check_and_insert(1).unwrap());
+    }
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn sled_store_fresh_is_separate() {
+        let store = SledStore::temporary().unwrap();
+        store.check_and_insert(1).unwrap();
+
+        let fresh = store.fresh().unwrap();
+        assert!(!fresh.check_and_insert(1).unwrap());
+        assert!(!store.check_and_insert(2).unwrap());
+        assert!(!fresh.check_and_insert(2).unwrap());
+        assert!(store.check_and_insert(1).unwrap());
+
+        let trees = store.db.tree_names().len();
+        drop(fresh);
+        assert_eq!(store.db.tree_names().len(), trees - 1);
+    }
+}
@@ -0,0 +1,316 @@
+//! Canonicalizes URLs so the different ways of writing the same page
+//! down can be recognised as one when deduplicating links.
+
+use crate::Url;
+
+/// Rewrites URLs into a canonical form before links are deduplicated,
+/// set on a client using
+/// [HttpStorkClientBuilder::canonicalizer](crate::HttpStorkClientBuilder::canonicalizer).
+///
+/// Links are still fetched from the URL they were found with, the
+/// canonical URL is only used to decide whether two links point to the
+/// same page, see [Link::canonical_url](crate::Link::canonical_url).
+///
+/// Lowercasing the scheme and host and removing default ports is done
+/// by [Url] as soon as a URL is parsed, on top of that and by default:
+/// - tracking parameters (`utm_*`, `fbclid`, `gclid`...) are stripped
+///   from the query string,
+/// - the remaining parameters are sorted by name,
+/// - percent-encoded unreserved characters are decoded and any other
+///   percent-encoding is uppercased,
+/// - the fragment is removed.
+///
+/// Folding trailing slashes and index files (ie. `/index.html` to `/`)
+/// can be turned on too, but aren't by default since plenty of servers
+/// serve different pages for each.
+///
+/// ```
+/// # use stork_http::{Canonicalizer, Url};
+/// let canonicalizer = Canonicalizer::default().fold_index_files(vec!["index.html"]);
+/// let url = Url::parse("https://example.com/index.html?b=1&utm_source=x&a=%7e").unwrap();
+///
+/// assert_eq!(
+///     canonicalizer.apply(&url).as_str(),
+///     "https://example.com/?a=~&b=1"
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Canonicalizer {
+    strip_params: Vec<String>,
+    sort_query: bool,
+    normalize_encoding: bool,
+    fold_trailing_slash: bool,
+    index_files: Vec<String>,
+}
+
+impl Default for Canonicalizer {
+    fn default() -> Self {
+        Self {
+            strip_params: [
+                "utm_*", "fbclid", "gclid", "dclid", "msclkid", "mc_cid", "mc_eid", "_ga", "yclid",
+                "igshid",
+            ]
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+            sort_query: true,
+            normalize_encoding: true,
+            fold_trailing_slash: false,
+            index_files: Vec::new(),
+        }
+    }
+}
+
+impl Canonicalizer {
+    /// Replaces the query parameters that are stripped from URLs. A name
+    /// ending in `*` strips every parameter starting with the rest of
+    /// the name, ie. `utm_*`. Pass an empty list to keep every
+    /// parameter.
+    pub fn strip_params<I, V>(mut self, params: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<String>,
+    {
+        self.strip_params = params.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets whether query parameters should be sorted by name, defaults
+    /// to true. Parameters with the same name are kept in their original
+    /// order.
+    pub fn sort_query(mut self, sort: bool) -> Self {
+        self.sort_query = sort;
+        self
+    }
+
+    /// Sets whether percent-encoding should be normalized, defaults to
+    /// true.
+    pub fn normalize_encoding(mut self, normalize: bool) -> Self {
+        self.normalize_encoding = normalize;
+        self
+    }
+
+    /// Sets whether a trailing slash should be removed from the path
+    /// (other than from the root, `/`), defaults to false.
+    pub fn fold_trailing_slash(mut self, fold: bool) -> Self {
+        self.fold_trailing_slash = fold;
+        self
+    }
+
+    /// Sets the file names that are treated as the index of their
+    /// directory, so `/docs/index.html` becomes `/docs/`. None by
+    /// default.
+    pub fn fold_index_files<I, V>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<String>,
+    {
+        self.index_files = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Gets the canonical form of `url`.
+    pub fn apply(&self, url: &Url) -> Url {
+        let mut url = url.clone();
+        url.set_fragment(None);
+
+        // urls like mailto: don't have a path we can do anything with
+        if url.cannot_be_a_base() {
+            return url;
+        }
+
+        let mut path = url.path().to_string();
+        if self.normalize_encoding {
+            path = normalize_encoding(&path);
+        }
+        if let Some(file_name) = path.rsplit('/').next() {
+            if self.index_files.iter().any(|index| index == file_name) {
+                path.truncate(path.len() - file_name.len());
+            }
+        }
+        if self.fold_trailing_slash && path.len() > 1 && path.ends_with('/') {
+            path.pop();
+        }
+        url.set_path(&path);
+
+        let query = url.query().map(|query| self.canonical_query(query));
+        url.set_query(query.as_deref().filter(|query| !query.is_empty()));
+
+        url
+    }
+
+    fn canonical_query(&self, query: &str) -> String {
+        let mut params = query
+            .split('&')
+            .filter(|param| !param.is_empty())
+            .map(|param| {
+                if self.normalize_encoding {
+                    normalize_encoding(param)
+                } else {
+                    param.to_string()
+                }
+            })
+            .filter(|param| !self.is_stripped(param_name(param)))
+            .collect::<Vec<_>>();
+
+        if self.sort_query {
+            // stable so repeated parameters keep their order
+            params.sort_by(|a, b| param_name(a).cmp(param_name(b)));
+        }
+
+        params.join("&")
+    }
+
+    fn is_stripped(&self, name: &str) -> bool {
+        self.strip_params.iter().any(|strip| {
+            if strip.ends_with('*') {
+                name.starts_with(&strip[..strip.len() - 1])
+            } else {
+                name == strip
+            }
+        })
+    }
+}
+
+/// Gets the name of a `name=value` query parameter.
+fn param_name(param: &str) -> &str {
+    param.split('=').next().unwrap_or(param)
+}
+
+/// Decodes percent-encoded unreserved characters (which never need
+/// encoding) and uppercases any other percent-encoding.
+fn normalize_encoding(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut normalized = String::with_capacity(value.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let decoded = if bytes[i] == b'%' {
+            value
+                .get(i + 1..i + 3)
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+
+        match decoded {
+            Some(byte) if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) => {
+                normalized.push(byte as char);
+                i += 3;
+            }
+            Some(byte) => {
+                normalized.push_str(&format!("%{:02X}", byte));
+                i += 3;
+            }
+            None => {
+                let c = value[i..].chars().next().unwrap();
+                normalized.push(c);
+                i += c.len_utf8();
+            }
+        }
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(canonicalizer: &Canonicalizer, url: &str) -> String {
+        canonicalizer.apply(&Url::parse(url).unwrap()).to_string()
+    }
+
+    #[test]
+    fn defaults() {
+        let canonicalizer = Canonicalizer::default();
+
+        assert_eq!(
+            apply(
+                &canonicalizer,
+                "HTTPS://Example.COM:443/a?utm_source=x&fbclid=y#top"
+            ),
+            "https://example.com/a"
+        );
+        assert_eq!(
+            apply(&canonicalizer, "https://example.com/?b=2&a=1&b=1"),
+            "https://example.com/?a=1&b=2&b=1"
+        );
+        assert_eq!(
+            apply(
+                &canonicalizer,
+                "https://example.com/%7euser/%c3%a9?q=%7e%2f"
+            ),
+            "https://example.com/~user/%C3%A9?q=~%2F"
+        );
+        // neither of these are folded unless asked for
+        assert_eq!(
+            apply(&canonicalizer, "https://example.com/docs/index.html"),
+            "https://example.com/docs/index.html"
+        );
+        assert_eq!(
+            apply(&canonicalizer, "https://example.com/docs/"),
+            "https://example.com/docs/"
+        );
+    }
+
+    #[test]
+    fn folding() {
+        let canonicalizer = Canonicalizer::default()
+            .fold_trailing_slash(true)
+            .fold_index_files(vec!["index.html"]);
+
+        assert_eq!(
+            apply(&canonicalizer, "https://example.com/docs/"),
+            "https://example.com/docs"
+        );
+        assert_eq!(
+            apply(&canonicalizer, "https://example.com/docs/index.html"),
+            "https://example.com/docs"
+        );
+        assert_eq!(
+            apply(&canonicalizer, "https://example.com/index.html"),
+            "https://example.com/"
+        );
+        assert_eq!(
+            apply(&canonicalizer, "https://example.com/notindex.html"),
+            "https://example.com/notindex.html"
+        );
+    }
+
+    #[test]
+    fn options_can_be_turned_off() {
+        let canonicalizer = Canonicalizer::default()
+            .strip_params(Vec::<String>::new())
+            .sort_query(false)
+            .normalize_encoding(false);
+
+        assert_eq!(
+            apply(
+                &canonicalizer,
+                "https://example.com/%7e?utm_source=x&b=1&a=%7e"
+            ),
+            "https://example.com/%7e?utm_source=x&b=1&a=%7e"
+        );
+    }
+
+    #[test]
+    fn empty_query_is_removed() {
+        assert_eq!(
+            apply(
+                &Canonicalizer::default(),
+                "https://example.com/a?utm_medium=x&&"
+            ),
+            "https://example.com/a"
+        );
+    }
+
+    #[test]
+    fn cannot_be_a_base() {
+        assert_eq!(
+            apply(&Canonicalizer::default(), "mailto:someone@example.com"),
+            "mailto:someone@example.com"
+        );
+    }
+}
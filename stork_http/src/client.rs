@@ -0,0 +1,948 @@
+use crate::canonical::Canonicalizer;
+use crate::extract::{find_links, has_links};
+use crate::filters::{RelFilter, RobotsFilter};
+use crate::limits::{ConcurrencyLimiter, Permit};
+use crate::page::{Page, Redirect};
+use crate::politeness::{self, RateLimit, Throttle};
+use crate::retry::RetryPolicy;
+use crate::robots::{Robots, RobotsCache};
+use crate::sitemap::{self, Sitemap};
+use crate::{Link, LinkKind, ReqwestClient, StorkHttpError, Url};
+
+use stork::filters::Filter;
+use stork::{ClientStream, Found, StorkClient};
+
+use async_stream::stream;
+
+use reqwest::header::{HeaderName, ETAG, LAST_MODIFIED, LOCATION};
+use reqwest::{Method, Response};
+
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Largest `robots.txt` we'll bother parsing, anything after this is
+/// ignored.
+const MAX_ROBOTS_TXT_SIZE: usize = 500 * 1024;
+
+/// Most redirects we'll follow when fetching `robots.txt`, as
+/// recommended by RFC 9309.
+const MAX_ROBOTS_TXT_REDIRECTS: usize = 5;
+
+#[derive(Clone)]
+pub struct HttpStorkClient {
+    inner: Arc<ClientInner>,
+}
+
+/// Everything a [HttpStorkClient] needs to make requests, shared with
+/// the streams it returns.
+struct ClientInner {
+    client: ReqwestClient,
+    user_agent: String,
+    limiter: ConcurrencyLimiter,
+    throttle: Throttle,
+    max_crawl_delay: Duration,
+    retry_policy: RetryPolicy,
+    read_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    max_body_size: Option<usize>,
+    truncate_oversized_bodies: bool,
+    robots: Option<Arc<RobotsCache>>,
+    robots_exempt_hosts: HashSet<String>,
+    nofollow_filter: Option<RelFilter<'static>>,
+    link_kinds: HashSet<LinkKind>,
+    follow_redirects: bool,
+    max_redirects: usize,
+    recorded_headers: Vec<HeaderName>,
+    canonicalizer: Option<Canonicalizer>,
+}
+
+impl HttpStorkClient {
+    pub fn new(client: ReqwestClient) -> Self {
+        Self::builder().client(client).build()
+    }
+
+    /// Creates a [HttpStorkClientBuilder] to configure a new
+    /// [HttpStorkClient].
+    pub fn builder() -> HttpStorkClientBuilder {
+        HttpStorkClientBuilder::default()
+    }
+
+    /// Creates a [RobotsFilter] which filters out any links this client
+    /// has found to be disallowed by their host's `robots.txt`.
+    ///
+    /// If `robots.txt` isn't being respected by this client, the
+    /// returned filter will match every link.
+    pub fn robots_filter(&self) -> RobotsFilter {
+        RobotsFilter::new(self.inner.robots.clone(), self.inner.user_agent.clone())
+    }
+
+    /// Finds the sitemaps for `url`'s origin, as declared in its
+    /// `robots.txt`, falling back to `/sitemap.xml` if it doesn't
+    /// declare any.
+    ///
+    /// Storking the returned [Link]s will yield the pages (or, for a
+    /// sitemap index, further sitemaps) listed in each sitemap.
+    pub async fn sitemaps(&self, url: &Url) -> Vec<Link> {
+        let robots = match &self.inner.robots {
+            Some(cache) => {
+                cache
+                    .get_or_fetch(url, || self.inner.fetch_robots_and_delay(url))
+                    .await
+            }
+            None => Arc::new(self.inner.fetch_robots(url).await),
+        };
+
+        if robots.sitemaps().is_empty() {
+            url.join("/sitemap.xml")
+                .map(Link::from)
+                .into_iter()
+                .collect()
+        } else {
+            robots.sitemaps().iter().cloned().map(Link::from).collect()
+        }
+    }
+
+    /// Checks if `url` is reachable without looking for any links on it,
+    /// using a `HEAD` request or a `GET` if the host doesn't give a
+    /// successful response to the `HEAD`. The response's body is never
+    /// read.
+    ///
+    /// Redirects are followed just like [StorkClient::run], the returned
+    /// [Page] describes the final response.
+    pub async fn check(&self, url: &Url) -> Result<Page, StorkHttpError> {
+        let inner = &self.inner;
+
+        let head = inner.fetch(Method::HEAD, url).await?;
+        let status = head.response.status();
+
+        // plenty of hosts don't implement HEAD properly, so give them
+        // another chance before calling the url broken
+        let fetched = if status.is_client_error() || status.is_server_error() {
+            drop(head);
+            inner.fetch(Method::GET, url).await?
+        } else {
+            head
+        };
+
+        Ok(fetched.page(&inner.recorded_headers))
+    }
+}
+
+impl Default for HttpStorkClient {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// Builder for a [HttpStorkClient], created using
+/// [HttpStorkClient::builder].
+pub struct HttpStorkClientBuilder {
+    client: Option<ReqwestClient>,
+    user_agent: String,
+    max_concurrent_requests: Option<usize>,
+    max_concurrent_requests_per_host: Option<usize>,
+    rate_limit: Option<RateLimit>,
+    crawl_delay: Duration,
+    max_crawl_delay: Duration,
+    retry_policy: RetryPolicy,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    max_body_size: Option<usize>,
+    truncate_oversized_bodies: bool,
+    respect_robots_txt: bool,
+    robots_exempt_hosts: HashSet<String>,
+    include_nofollow: bool,
+    link_kinds: HashSet<LinkKind>,
+    follow_redirects: bool,
+    max_redirects: usize,
+    recorded_headers: Vec<HeaderName>,
+    canonicalizer: Option<Canonicalizer>,
+}
+
+impl Default for HttpStorkClientBuilder {
+    fn default() -> Self {
+        Self {
+            client: None,
+            user_agent: concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).to_string(),
+            max_concurrent_requests: None,
+            max_concurrent_requests_per_host: None,
+            rate_limit: None,
+            crawl_delay: Duration::from_secs(0),
+            max_crawl_delay: Duration::from_secs(60),
+            retry_policy: RetryPolicy::default(),
+            connect_timeout: Some(Duration::from_secs(10)),
+            read_timeout: Some(Duration::from_secs(30)),
+            timeout: None,
+            max_body_size: Some(10 * 1024 * 1024),
+            truncate_oversized_bodies: false,
+            respect_robots_txt: true,
+            robots_exempt_hosts: HashSet::new(),
+            include_nofollow: false,
+            link_kinds: std::iter::once(LinkKind::Anchor).collect(),
+            follow_redirects: true,
+            max_redirects: 10,
+            recorded_headers: vec![LAST_MODIFIED, ETAG],
+            canonicalizer: None,
+        }
+    }
+}
+
+impl HttpStorkClientBuilder {
+    /// Replaces the default [ReqwestClient] used to make requests.
+    ///
+    /// The user agent set using [HttpStorkClientBuilder::user_agent]
+    /// won't be applied to a custom client, so should be set to match
+    /// for `robots.txt` to be evaluated correctly. A custom client should
+    /// also be built with a [reqwest::redirect::Policy::none] for
+    /// redirects to be tracked.
+    pub fn client(mut self, client: ReqwestClient) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Sets the user agent requests are sent with, which is also the
+    /// user agent `robots.txt` rules are evaluated for. Defaults to
+    /// `stork_http/<version>`.
+    pub fn user_agent<V: Into<String>>(mut self, user_agent: V) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Sets the maximum number of requests that can be in flight at
+    /// once across all hosts. Unlimited by default.
+    pub fn max_concurrent_requests(mut self, max: usize) -> Self {
+        self.max_concurrent_requests = Some(max);
+        self
+    }
+
+    /// Sets the maximum number of requests that can be in flight to
+    /// any one host at once. Unlimited by default.
+    pub fn max_concurrent_requests_per_host(mut self, max: usize) -> Self {
+        self.max_concurrent_requests_per_host = Some(max);
+        self
+    }
+
+    /// Limits the rate of requests to any one host using a token
+    /// bucket, allowing bursts of up to `burst` requests before settling
+    /// at `requests_per_second`. Unlimited by default.
+    pub fn rate_limit_per_host(mut self, requests_per_second: f64, burst: u32) -> Self {
+        assert!(
+            requests_per_second > 0.0,
+            "requests per second must be greater than 0"
+        );
+
+        self.rate_limit = Some(RateLimit {
+            requests_per_second,
+            burst: f64::from(burst.max(1)),
+        });
+        self
+    }
+
+    /// Sets the minimum amount of time to wait between sending requests
+    /// to the same host. No delay by default.
+    ///
+    /// Hosts asking for a longer delay using `Crawl-delay` in their
+    /// `robots.txt` will have that respected instead, up to
+    /// [HttpStorkClientBuilder::max_crawl_delay].
+    pub fn crawl_delay(mut self, delay: Duration) -> Self {
+        self.crawl_delay = delay;
+        self
+    }
+
+    /// Sets the longest `Crawl-delay` we'll respect from a host's
+    /// `robots.txt`, defaults to 60 seconds. Hosts asking for any longer
+    /// than this will be crawled at this delay instead, so a broken
+    /// `robots.txt` can't stall the crawl.
+    pub fn max_crawl_delay(mut self, max: Duration) -> Self {
+        self.max_crawl_delay = max;
+        self
+    }
+
+    /// Sets which failed requests are retried and how long to wait
+    /// before retrying them, see [RetryPolicy] for the defaults.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets how long we'll wait to connect to a host before giving up,
+    /// defaults to 10 seconds. Pass `None` to wait forever.
+    ///
+    /// Like the user agent, this won't be applied to a custom
+    /// [ReqwestClient] set using [HttpStorkClientBuilder::client].
+    pub fn connect_timeout<T: Into<Option<Duration>>>(mut self, timeout: T) -> Self {
+        self.connect_timeout = timeout.into();
+        self
+    }
+
+    /// Sets how long we'll wait to hear anything from a host, either the
+    /// start of a response once we've sent the request (including
+    /// connecting) or the next part of its body, before giving up on
+    /// it. Defaults to 30 seconds, pass `None` to wait forever.
+    pub fn read_timeout<T: Into<Option<Duration>>>(mut self, timeout: T) -> Self {
+        self.read_timeout = timeout.into();
+        self
+    }
+
+    /// Sets how long a request can take altogether, from sending it to
+    /// reading the last of its body, before giving up on it. Each retry
+    /// and each redirect followed gets the full timeout to itself. None
+    /// by default, since a large page trickling in steadily is still
+    /// making progress.
+    pub fn timeout<T: Into<Option<Duration>>>(mut self, timeout: T) -> Self {
+        self.timeout = timeout.into();
+        self
+    }
+
+    /// Sets the most bytes of a response's body we'll read, after
+    /// decompression. Defaults to 10 MiB, pass `None` to read bodies no
+    /// matter how large. Anything that could be a sitemap is always
+    /// allowed the 50 MB sitemaps can be.
+    ///
+    /// A body larger than this fails with a
+    /// [StorkHttpError::BodyTooLarge], unless
+    /// [HttpStorkClientBuilder::truncate_oversized_bodies] is set.
+    pub fn max_body_size<T: Into<Option<usize>>>(mut self, max: T) -> Self {
+        self.max_body_size = max.into();
+        self
+    }
+
+    /// Sets whether a body larger than the
+    /// [max body size](HttpStorkClientBuilder::max_body_size) should be
+    /// cut short and have the links found up to that point yielded,
+    /// rather than failing. Defaults to false.
+    ///
+    /// A truncated page can be recognised using [Page::is_truncated].
+    pub fn truncate_oversized_bodies(mut self, truncate: bool) -> Self {
+        self.truncate_oversized_bodies = truncate;
+        self
+    }
+
+    /// Sets whether hosts' `robots.txt` should be fetched and respected,
+    /// defaults to true.
+    ///
+    /// Whilst respected, pages disallowed for our user agent won't be
+    /// fetched and any `Crawl-delay` will be honoured. Use
+    /// [HttpStorkClient::robots_filter] to stop disallowed links being
+    /// yielded in the first place.
+    pub fn respect_robots_txt(mut self, respect: bool) -> Self {
+        self.respect_robots_txt = respect;
+        self
+    }
+
+    /// Exempts the given host from `robots.txt` checks, useful for
+    /// crawling internal sites which disallow everything.
+    pub fn robots_txt_exempt_host<V: Into<String>>(mut self, host: V) -> Self {
+        self.robots_exempt_hosts.insert(host.into());
+        self
+    }
+
+    /// Sets whether links marked `rel="nofollow"` should be yielded,
+    /// defaults to false.
+    ///
+    /// Once included, [RelFilter] can be used to decide which links to
+    /// follow based on their `rel`.
+    pub fn include_nofollow(mut self, include: bool) -> Self {
+        self.include_nofollow = include;
+        self
+    }
+
+    /// Sets which kinds of links should be looked for within HTML
+    /// pages, defaults to just [LinkKind::Anchor]. Pass [LinkKind::HTML]
+    /// to look for every kind of link.
+    ///
+    /// URLs listed in sitemaps are always yielded regardless.
+    pub fn link_kinds<I: IntoIterator<Item = LinkKind>>(mut self, kinds: I) -> Self {
+        self.link_kinds = kinds.into_iter().collect();
+        self
+    }
+
+    /// Sets whether redirects should be followed, defaults to true.
+    ///
+    /// When not followed, the target of a redirect is yielded as a
+    /// [LinkKind::Redirect] link instead.
+    pub fn follow_redirects(mut self, follow: bool) -> Self {
+        self.follow_redirects = follow;
+        self
+    }
+
+    /// Sets the most redirects that will be followed for a single link
+    /// before giving up on it, defaults to 10.
+    pub fn max_redirects(mut self, max: usize) -> Self {
+        self.max_redirects = max;
+        self
+    }
+
+    /// Records the given response header on each [Page] fetched, on top
+    /// of `Last-Modified` and `ETag` which are always recorded.
+    pub fn record_header(mut self, name: HeaderName) -> Self {
+        if !self.recorded_headers.contains(&name) {
+            self.recorded_headers.push(name);
+        }
+        self
+    }
+
+    /// Canonicalizes the URLs of every link found using the given
+    /// [Canonicalizer], so links to the same page written down in
+    /// different ways are deduplicated. Links aren't canonicalized by
+    /// default.
+    ///
+    /// The root of a crawl should be canonicalized too, using
+    /// [Link::canonicalize].
+    pub fn canonicalizer(mut self, canonicalizer: Canonicalizer) -> Self {
+        self.canonicalizer = Some(canonicalizer);
+        self
+    }
+
+    pub fn build(self) -> HttpStorkClient {
+        let user_agent = self.user_agent;
+        let connect_timeout = self.connect_timeout;
+        let client = self.client.unwrap_or_else(|| {
+            // redirects are followed by us rather than reqwest so we can
+            // keep track of them
+            let mut builder = reqwest::Client::builder()
+                .user_agent(user_agent.as_str())
+                .redirect(reqwest::redirect::Policy::none());
+            if let Some(timeout) = connect_timeout {
+                builder = builder.connect_timeout(timeout);
+            }
+
+            builder.build().unwrap()
+        });
+
+        HttpStorkClient {
+            inner: Arc::new(ClientInner {
+                client,
+                user_agent,
+                limiter: ConcurrencyLimiter::new(
+                    self.max_concurrent_requests,
+                    self.max_concurrent_requests_per_host,
+                ),
+                throttle: Throttle::new(self.rate_limit, self.crawl_delay),
+                max_crawl_delay: self.max_crawl_delay,
+                retry_policy: self.retry_policy,
+                read_timeout: self.read_timeout,
+                timeout: self.timeout,
+                max_body_size: self.max_body_size,
+                truncate_oversized_bodies: self.truncate_oversized_bodies,
+                robots: if self.respect_robots_txt {
+                    Some(Arc::new(RobotsCache::default()))
+                } else {
+                    None
+                },
+                robots_exempt_hosts: self.robots_exempt_hosts,
+                nofollow_filter: if self.include_nofollow {
+                    None
+                } else {
+                    Some(RelFilter::exclude("nofollow"))
+                },
+                link_kinds: self.link_kinds,
+                follow_redirects: self.follow_redirects,
+                max_redirects: self.max_redirects,
+                recorded_headers: self.recorded_headers,
+                canonicalizer: self.canonicalizer,
+            }),
+        }
+    }
+}
+
+impl StorkClient<Link> for HttpStorkClient {
+    type Error = StorkHttpError;
+    type Page = Page;
+    type Stream = ClientStream<Found<Link, Page>, StorkHttpError>;
+
+    fn run(&self, src: &Link) -> Self::Stream {
+        let src = src.clone();
+        let inner = Arc::clone(&self.inner);
+
+        Box::pin(stream! {
+            let (page, links) = match inner.fetch_links(&src).await {
+                Ok(fetched) => fetched,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+            yield Ok(Found::Page(page));
+
+            // a link we couldn't understand shouldn't cost us the rest of
+            // the page, so errors are passed on and we carry on
+            for link in links {
+                match link {
+                    Ok(link) => {
+                        if inner.nofollow_filter.as_ref().is_none_or(|filter| filter.matches(&link)) {
+                            yield Ok(Found::Child(inner.canonicalize(link)));
+                        }
+                    }
+                    Err(e) => yield Err(e),
+                }
+            }
+        })
+    }
+}
+
+impl ClientInner {
+    /// Fetches the page `src` points to and finds the links on it,
+    /// returning them alongside the [Page] describing the response. Fails
+    /// if the page itself couldn't be fetched, whilst links on the page
+    /// that couldn't be understood are returned as errors in their place.
+    async fn fetch_links(
+        &self,
+        src: &Link,
+    ) -> Result<(Page, Vec<Result<Link, StorkHttpError>>), StorkHttpError> {
+        let mut attempts = 0;
+
+        // the connection could drop whilst we're reading the body, in
+        // which case we'll need to go back and request it all over again
+        let (document_url, doc, page) = loop {
+            let fetched = self.fetch(Method::GET, &src.url).await?;
+            attempts += fetched.attempts;
+
+            let mut page = fetched.page(&self.recorded_headers);
+            page.attempts = attempts;
+
+            // if we've been left with a redirect it's because we're not
+            // following them, so its target is the only link there is
+            if let Some(target) = redirect_target(&fetched.response) {
+                return Ok((
+                    page,
+                    vec![Ok(Link {
+                        kind: Some(LinkKind::Redirect),
+                        ..Link::from(target)
+                    })],
+                ));
+            }
+
+            // relative links are relative to wherever we ended up after
+            // any redirects rather than the url we asked for
+            let document_url = fetched.response.url().clone();
+            let Fetched {
+                response,
+                permit,
+                sent_at,
+                ..
+            } = fetched;
+
+            // we won't know it's a sitemap until we've read it, so give
+            // anything that could be one the room sitemaps are allowed
+            let max_body_size =
+                sitemap::max_body_size(&document_url, page.content_type(), self.max_body_size);
+
+            // TODO: can we get this to stream into the Document? need some
+            // TODO: compat layer between futures and std::io::Read
+            let body = self
+                .read_body(
+                    response,
+                    sent_at,
+                    max_body_size,
+                    self.truncate_oversized_bodies,
+                )
+                .await;
+
+            match body {
+                Ok(Body { bytes, truncated }) => {
+                    // we're done with the network, let someone else have a go
+                    drop(permit);
+
+                    page.duration = sent_at.elapsed();
+                    page.content_length.get_or_insert(bytes.len() as u64);
+                    page.truncated = truncated;
+
+                    break (document_url, bytes, page);
+                }
+                Err(e) if self.retry_policy.retries_error(&e, attempts) => {
+                    drop(permit);
+                    tokio::time::delay_for(self.retry_policy.backoff(attempts)).await;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        // sitemaps get their listed urls yielded and html gets searched
+        // for links, whereas there's nothing we know how to find links
+        // in amongst anything else (images, pdfs and so on)
+        let links = match Sitemap::parse(&doc[..]) {
+            Some(sitemap) => sitemap
+                .into_urls()
+                .into_iter()
+                .map(|url| {
+                    Ok(Link {
+                        kind: Some(LinkKind::Sitemap),
+                        ..Link::from(url)
+                    })
+                })
+                .collect(),
+            None if has_links(page.content_type()) => {
+                find_links(&document_url, &doc[..], &self.link_kinds)?
+            }
+            None => Vec::new(),
+        };
+
+        Ok((page, links))
+    }
+
+    /// Canonicalizes `link` if we've been given a [Canonicalizer].
+    fn canonicalize(&self, link: Link) -> Link {
+        match &self.canonicalizer {
+            Some(canonicalizer) => link.canonicalize(canonicalizer),
+            None => link,
+        }
+    }
+
+    /// Requests `url` using [ClientInner::request], following any
+    /// redirects (unless told not to) and checking each hop against
+    /// `robots.txt`.
+    ///
+    /// Returns the final response as returned by [ClientInner::request],
+    /// alongside the redirects that were followed to get to it,
+    /// including the response's own redirect if redirects aren't being
+    /// followed.
+    async fn fetch(&self, method: Method, url: &Url) -> Result<Fetched, StorkHttpError> {
+        let mut url = url.clone();
+        let mut redirects = Vec::new();
+
+        loop {
+            // a redirect could take us somewhere we're not allowed to go
+            if !self.is_allowed_by_robots(&url).await {
+                return Err(StorkHttpError::RobotsDisallowed { url });
+            }
+
+            let mut fetched = self.request(method.clone(), &url).await?;
+
+            let target = match redirect_target(&fetched.response) {
+                Some(target) => target,
+                None => {
+                    fetched.redirects = redirects;
+                    return Ok(fetched);
+                }
+            };
+
+            redirects.push(Redirect {
+                url,
+                status: fetched.response.status(),
+            });
+
+            if !self.follow_redirects {
+                fetched.redirects = redirects;
+                return Ok(fetched);
+            }
+
+            if redirects.len() > self.max_redirects {
+                return Err(StorkHttpError::TooManyRedirects {
+                    url: redirects.swap_remove(0).url,
+                    redirects: self.max_redirects,
+                });
+            }
+
+            url = target;
+        }
+    }
+
+    /// Sends a `method` request to `url` once the host's politeness
+    /// constraints allow, retrying according to our [RetryPolicy] if it
+    /// fails or the host asks us to slow down.
+    ///
+    /// The returned [Fetched] won't have any redirects filled in, and its
+    /// [Permit] should be held until the response body has been read.
+    async fn request(&self, method: Method, url: &Url) -> Result<Fetched, StorkHttpError> {
+        let mut attempt = 1;
+
+        loop {
+            // wait our turn for the host before taking up one of the
+            // concurrency slots
+            self.throttle.wait(url).await;
+            let permit = self.limiter.acquire(url).await;
+
+            let sent_at = Instant::now();
+            let response = self
+                .within(
+                    url,
+                    sent_at,
+                    self.client.request(method.clone(), url.clone()).send(),
+                )
+                .await
+                .and_then(|response| {
+                    response.map_err(|source| StorkHttpError::HttpError {
+                        url: url.clone(),
+                        status: source.status(),
+                        source,
+                    })
+                });
+
+            let response = match response {
+                Ok(response) => response,
+                Err(e) if self.retry_policy.retries_error(&e, attempt) => {
+                    drop(permit);
+                    tokio::time::delay_for(self.retry_policy.backoff(attempt)).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            let status = response.status();
+            let delay = politeness::retry_after(&response)
+                .unwrap_or_else(|| self.retry_policy.backoff(attempt));
+
+            // a host telling us to slow down won't want to hear from any
+            // of our other requests either, whether or not we're going to
+            // retry this one
+            let throttled = politeness::is_throttled(status);
+            if throttled {
+                self.throttle.back_off(url, delay);
+            }
+
+            if self.retry_policy.retries_status(status, attempt) {
+                // if the host wants us to wait any longer than this then
+                // we'll just have to make do with the response we've got
+                if !self.retry_policy.is_too_long(delay) {
+                    drop(response);
+                    drop(permit);
+
+                    // the throttle will already hold us back if we've
+                    // backed off from the host
+                    if !throttled {
+                        tokio::time::delay_for(delay).await;
+                    }
+
+                    attempt += 1;
+                    continue;
+                }
+            }
+
+            return Ok(Fetched {
+                response,
+                permit,
+                sent_at,
+                attempts: attempt,
+                redirects: Vec::new(),
+            });
+        }
+    }
+
+    /// Reads the body of `response` to a request sent at `sent_at`, up to
+    /// `max_size` bytes. A body larger than that is cut short if
+    /// `truncate` is set, and fails otherwise.
+    async fn read_body(
+        &self,
+        mut response: Response,
+        sent_at: Instant,
+        max_size: Option<usize>,
+        truncate: bool,
+    ) -> Result<Body, StorkHttpError> {
+        let url = response.url().clone();
+        let status = response.status();
+
+        // no point reading any of it if we've been told up front it's
+        // too big
+        if let Some(limit) = max_size.filter(|_| !truncate) {
+            if response
+                .content_length()
+                .is_some_and(|len| len > limit as u64)
+            {
+                return Err(StorkHttpError::BodyTooLarge { url, limit });
+            }
+        }
+
+        let mut bytes = Vec::new();
+        loop {
+            let chunk = self
+                .within(&url, sent_at, response.chunk())
+                .await?
+                .map_err(|source| StorkHttpError::HttpError {
+                    url: url.clone(),
+                    status: Some(status),
+                    source,
+                })?;
+
+            let chunk = match chunk {
+                Some(chunk) => chunk,
+                None => {
+                    return Ok(Body {
+                        bytes,
+                        truncated: false,
+                    })
+                }
+            };
+            bytes.extend_from_slice(&chunk);
+
+            match max_size {
+                Some(limit) if bytes.len() > limit && truncate => {
+                    bytes.truncate(limit);
+                    return Ok(Body {
+                        bytes,
+                        truncated: true,
+                    });
+                }
+                Some(limit) if bytes.len() > limit => {
+                    return Err(StorkHttpError::BodyTooLarge { url, limit });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Waits on `future`, part of a request to `url` sent at `sent_at`,
+    /// giving up with a [StorkHttpError::TimedOut] once it's taken
+    /// longer than the read timeout or the request as a whole has taken
+    /// longer than the timeout.
+    async fn within<F: Future>(
+        &self,
+        url: &Url,
+        sent_at: Instant,
+        future: F,
+    ) -> Result<F::Output, StorkHttpError> {
+        let remaining = self
+            .timeout
+            .map(|timeout| timeout.checked_sub(sent_at.elapsed()).unwrap_or_default());
+        let wait = match (self.read_timeout, remaining) {
+            (Some(read), Some(remaining)) => read.min(remaining),
+            (Some(wait), None) | (None, Some(wait)) => wait,
+            (None, None) => return Ok(future.await),
+        };
+
+        tokio::time::timeout(wait, future)
+            .await
+            .map_err(|_| StorkHttpError::TimedOut {
+                url: url.clone(),
+                after: sent_at.elapsed(),
+            })
+    }
+
+    /// Checks `url` against its host's `robots.txt`, fetching it first
+    /// if we haven't already.
+    async fn is_allowed_by_robots(&self, url: &Url) -> bool {
+        let cache = match &self.robots {
+            Some(cache) => cache,
+            None => return true,
+        };
+
+        if url
+            .host_str()
+            .is_none_or(|host| self.robots_exempt_hosts.contains(host))
+        {
+            return true;
+        }
+
+        let robots = cache
+            .get_or_fetch(url, || self.fetch_robots_and_delay(url))
+            .await;
+        robots.is_allowed(&self.user_agent, url)
+    }
+
+    /// Fetches the `robots.txt` for `url`'s origin using
+    /// [ClientInner::fetch_robots], and applies any `Crawl-delay` it
+    /// asks for.
+    async fn fetch_robots_and_delay(&self, url: &Url) -> Robots {
+        let robots = self.fetch_robots(url).await;
+
+        if let Some(delay) = robots.crawl_delay(&self.user_agent) {
+            self.throttle
+                .set_min_delay(url, delay.min(self.max_crawl_delay));
+        }
+
+        robots
+    }
+
+    /// Fetches and parses the `robots.txt` for `url`'s origin.
+    async fn fetch_robots(&self, url: &Url) -> Robots {
+        let robots_url = match url.join("/robots.txt") {
+            Ok(robots_url) => robots_url,
+            Err(_) => return Robots::allow_all(),
+        };
+
+        let mut fetched = self.request(Method::GET, &robots_url).await;
+        for _ in 0..MAX_ROBOTS_TXT_REDIRECTS {
+            let target = match fetched
+                .as_ref()
+                .ok()
+                .and_then(|f| redirect_target(&f.response))
+            {
+                Some(target) => target,
+                None => break,
+            };
+
+            // the redirect is likely to the same host, so its permit has
+            // to be given up before we can get another
+            drop(fetched);
+            fetched = self.request(Method::GET, &target).await;
+        }
+
+        match fetched {
+            Ok(Fetched {
+                response,
+                permit: _permit,
+                sent_at,
+                ..
+            }) if response.status().is_success() => {
+                match self
+                    .read_body(response, sent_at, Some(MAX_ROBOTS_TXT_SIZE), true)
+                    .await
+                {
+                    Ok(body) => Robots::parse(&String::from_utf8_lossy(&body.bytes)),
+                    Err(_) => Robots::allow_all(),
+                }
+            }
+            // a robots.txt that doesn't exist doesn't disallow anything,
+            // but if the server is erroring we've no idea what it wants
+            // so we'll play it safe and keep away
+            Ok(Fetched { response, .. }) if response.status().is_server_error() => {
+                Robots::disallow_all()
+            }
+            Ok(_) => Robots::allow_all(),
+            // if we can't reach the host at all then there's no harm in
+            // letting the request for the page itself fail instead
+            Err(_) => Robots::allow_all(),
+        }
+    }
+}
+
+/// A response to a request sent by [ClientInner::request], along with
+/// everything we learnt whilst getting it.
+struct Fetched {
+    response: Response,
+    /// Should be held until the response's body has been read.
+    permit: Permit,
+    /// When the request that got the response was sent.
+    sent_at: Instant,
+    /// How many times the request had to be sent to get the response.
+    attempts: usize,
+    /// The redirects followed to get to the response, filled in by
+    /// [ClientInner::fetch].
+    redirects: Vec<Redirect>,
+}
+
+impl Fetched {
+    /// Records the details of the response as a [Page], timed up until
+    /// now.
+    fn page(&self, recorded: &[HeaderName]) -> Page {
+        let mut page = Page::from_response(&self.response, self.redirects.clone(), recorded);
+        page.duration = self.sent_at.elapsed();
+        page.attempts = self.attempts;
+        page
+    }
+}
+
+/// A response's body, as read by [ClientInner::read_body].
+struct Body {
+    bytes: Vec<u8>,
+    /// Whether the body was cut short for being too large.
+    truncated: bool,
+}
+
+/// Gets where `response` is redirecting us to, if it's a redirect with a
+/// `Location` we can understand.
+fn redirect_target(response: &Response) -> Option<Url> {
+    if !response.status().is_redirection() {
+        return None;
+    }
+
+    let location = response.headers().get(LOCATION)?.to_str().ok()?;
+    let mut target = response.url().join(location).ok()?;
+    target.set_fragment(None);
+
+    Some(target)
+}
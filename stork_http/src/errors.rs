@@ -1,9 +1,79 @@
-#[derive(Debug, Fail)]
+use crate::{StatusCode, Url};
+
+use std::time::Duration;
+
+#[derive(Debug, thiserror::Error)]
 pub enum StorkHttpError {
-    #[fail(display = "failed to parse url")]
-    UrlParseError,
-    #[fail(display = "failed to parse html")]
-    HtmlParseError,
-    #[fail(display = "failed to send http request")]
-    HttpError,
+    /// A URL couldn't be parsed, either a link found on a page or one
+    /// given to us directly.
+    #[error("failed to parse url {input:?}{}", base.as_ref().map(|base| format!(" found on {}", base)).unwrap_or_default())]
+    UrlParseError {
+        input: String,
+        /// The URL the link was being resolved against, usually the page
+        /// it was found on, if it was found on one.
+        base: Option<Url>,
+        #[source]
+        source: url::ParseError,
+    },
+    #[error("failed to parse html from {url}")]
+    HtmlParseError {
+        url: Url,
+        #[source]
+        source: std::io::Error,
+    },
+    /// The request couldn't be sent, or its response couldn't be read.
+    #[error("failed to send http request to {url}{}", status.map(|status| format!(" ({})", status)).unwrap_or_default())]
+    HttpError {
+        url: Url,
+        /// The status of the response, if we got as far as one.
+        status: Option<StatusCode>,
+        #[source]
+        source: reqwest::Error,
+    },
+    /// The host took longer than the
+    /// [read timeout](crate::HttpStorkClientBuilder::read_timeout) to send
+    /// us anything, or the whole request took longer than the
+    /// [timeout](crate::HttpStorkClientBuilder::timeout).
+    #[error("timed out fetching {url} after {after:?}")]
+    TimedOut { url: Url, after: Duration },
+    /// The response's body was larger than the
+    /// [max body size](crate::HttpStorkClientBuilder::max_body_size).
+    #[error("response from {url} is larger than the {limit} byte limit")]
+    BodyTooLarge { url: Url, limit: usize },
+    #[error("{url} is disallowed by robots.txt")]
+    RobotsDisallowed { url: Url },
+    #[error("too many redirects fetching {url}, gave up after {redirects}")]
+    TooManyRedirects { url: Url, redirects: usize },
+    #[error("failed to parse pattern {pattern:?}")]
+    PatternParseError {
+        pattern: String,
+        #[source]
+        source: regex::Error,
+    },
+}
+
+impl StorkHttpError {
+    /// Gets the URL the error happened whilst fetching, or for a
+    /// [StorkHttpError::UrlParseError], the page the URL was found on.
+    pub fn url(&self) -> Option<&Url> {
+        match self {
+            StorkHttpError::UrlParseError { base, .. } => base.as_ref(),
+            StorkHttpError::HtmlParseError { url, .. }
+            | StorkHttpError::HttpError { url, .. }
+            | StorkHttpError::TimedOut { url, .. }
+            | StorkHttpError::BodyTooLarge { url, .. }
+            | StorkHttpError::RobotsDisallowed { url }
+            | StorkHttpError::TooManyRedirects { url, .. } => Some(url),
+            StorkHttpError::PatternParseError { .. } => None,
+        }
+    }
+
+    /// Gets the status of the response the error happened whilst
+    /// reading, if there was one.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            StorkHttpError::HttpError { status, .. } => *status,
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,406 @@
+//! Finding links within HTML documents.
+
+use crate::{Link, LinkKind, StorkHttpError, Url};
+
+use select::document::Document;
+use select::node::Node;
+use select::predicate::{Attr, Element, Name, Predicate};
+
+use std::collections::HashSet;
+
+/// Finds all the links of the given `kinds` in the given HTML document,
+/// in the order they appear. `document_url` should be the URL the
+/// document was eventually served from, after any redirects.
+///
+/// This is done up front rather than lazily as links are yielded
+/// because a [Document] can't be sent between threads, and holding on
+/// to one would stop the stream from
+/// [HttpStorkClient::run](crate::HttpStorkClient) being [Send].
+pub(crate) fn find_links(
+    document_url: &Url,
+    html: &[u8],
+    kinds: &HashSet<LinkKind>,
+) -> Result<Vec<Result<Link, StorkHttpError>>, StorkHttpError> {
+    let document = Document::from_read(html).map_err(|source| StorkHttpError::HtmlParseError {
+        url: document_url.clone(),
+        source,
+    })?;
+    let root = &base_url(&document, document_url);
+
+    let mut links = Vec::new();
+    let mut push = |node: &Node, kind: LinkKind, href: &str, text: Option<String>| {
+        if !kinds.contains(&kind) || is_data_uri(href) {
+            return;
+        }
+
+        let rel = node
+            .attr("rel")
+            .map(|rel| {
+                rel.split_whitespace()
+                    .map(str::to_ascii_lowercase)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        links.push(resolve_href(root, href).map(|url| Link {
+            url,
+            text: text.filter(|x| !x.is_empty()),
+            rel,
+            kind: Some(kind),
+            canonical: None,
+        }));
+    };
+
+    for node in document.find(Element) {
+        let name = match node.name() {
+            Some(name) => name,
+            None => continue,
+        };
+
+        match name {
+            "a" => {
+                if let Some(href) = node.attr("href") {
+                    push(
+                        &node,
+                        LinkKind::Anchor,
+                        href,
+                        Some(node.text().trim().to_string()),
+                    );
+                }
+            }
+            "area" => {
+                if let Some(href) = node.attr("href") {
+                    push(
+                        &node,
+                        LinkKind::Area,
+                        href,
+                        node.attr("alt").map(str::to_string),
+                    );
+                }
+            }
+            "link" => {
+                if let Some(href) = node.attr("href") {
+                    push(
+                        &node,
+                        LinkKind::Link,
+                        href,
+                        node.attr("title").map(str::to_string),
+                    );
+                }
+            }
+            "iframe" => {
+                if let Some(src) = node.attr("src") {
+                    push(
+                        &node,
+                        LinkKind::Iframe,
+                        src,
+                        node.attr("title").map(str::to_string),
+                    );
+                }
+            }
+            "img" => {
+                let alt = node.attr("alt").map(str::to_string);
+
+                if let Some(src) = node.attr("src") {
+                    push(&node, LinkKind::Image, src, alt.clone());
+                }
+
+                for src in node.attr("srcset").map(parse_srcset).unwrap_or_default() {
+                    push(&node, LinkKind::ImageSrcset, src, alt.clone());
+                }
+            }
+            "script" => {
+                if let Some(src) = node.attr("src") {
+                    push(&node, LinkKind::Script, src, None);
+                }
+            }
+            "form" => {
+                if let Some(action) = node.attr("action") {
+                    push(&node, LinkKind::FormAction, action, None);
+                }
+            }
+            "meta" => {
+                let is_refresh = node
+                    .attr("http-equiv")
+                    .is_some_and(|v| v.trim().eq_ignore_ascii_case("refresh"));
+
+                if let Some(url) = node
+                    .attr("content")
+                    .filter(|_| is_refresh)
+                    .and_then(parse_meta_refresh)
+                {
+                    push(&node, LinkKind::MetaRefresh, url, None);
+                }
+            }
+            "style" => {
+                for url in parse_css_urls(&node.text()) {
+                    push(&node, LinkKind::Css, url, None);
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(style) = node.attr("style") {
+            for url in parse_css_urls(style) {
+                push(&node, LinkKind::Css, url, None);
+            }
+        }
+    }
+
+    Ok(links)
+}
+
+/// Checks if a response served with the given `Content-Type` could
+/// have links in it for [find_links] to find, ie. it's HTML or some
+/// other kind of XML. Responses without one are assumed to be HTML.
+pub(crate) fn has_links(content_type: Option<&str>) -> bool {
+    let mime = match content_type {
+        Some(content_type) => content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase(),
+        None => return true,
+    };
+
+    mime == "text/html" || mime.ends_with("/xml") || mime.ends_with("+xml")
+}
+
+/// Finds the URL relative links in the document should be resolved
+/// against, which is the `href` of the first `<base>` that has one
+/// (itself resolved against the document's URL) or the document's URL
+/// if there isn't one.
+fn base_url(document: &Document, document_url: &Url) -> Url {
+    document
+        .find(Name("base").and(Attr("href", ())))
+        .next()
+        .and_then(|base| document_url.join(base.attr("href")?.trim()).ok())
+        .unwrap_or_else(|| document_url.clone())
+}
+
+/// Resolves a (potentially relative) `href` against the base URL of the
+/// page it was found on.
+fn resolve_href(base: &Url, href: &str) -> Result<Url, StorkHttpError> {
+    // absolute urls ignore the base entirely when joined
+    let mut href = base
+        .join(href.trim())
+        .map_err(|source| StorkHttpError::UrlParseError {
+            input: href.to_string(),
+            base: Some(base.clone()),
+            source,
+        })?;
+    href.set_fragment(None);
+
+    Ok(href)
+}
+
+/// Inline `data:` URIs are the resource itself rather than a link to
+/// one, so there's no point yielding them.
+fn is_data_uri(href: &str) -> bool {
+    href.trim_start()
+        .get(..5)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:"))
+}
+
+/// Grabs the URLs from a `srcset` attribute, ie. `a.png` and `b.png`
+/// from `a.png 1x, b.png 2x`.
+fn parse_srcset(srcset: &str) -> Vec<&str> {
+    let mut urls = Vec::new();
+    let mut rest = srcset;
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        if rest.is_empty() {
+            break;
+        }
+
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let (url, after) = rest.split_at(end);
+
+        // urls can contain commas themselves, so only a trailing comma
+        // ends the candidate early (with no descriptors)
+        let trimmed = url.trim_end_matches(',');
+        urls.push(trimmed);
+
+        rest = if trimmed.len() != url.len() {
+            after
+        } else {
+            // skip over the descriptors to the next candidate
+            after.find(',').map_or("", |i| &after[i + 1..])
+        };
+    }
+
+    urls
+}
+
+/// Grabs the URL from the `content` of a `<meta http-equiv="refresh">`,
+/// ie. `/next` from `5; url=/next`.
+fn parse_meta_refresh(content: &str) -> Option<&str> {
+    let rest = content
+        .trim_start()
+        .trim_start_matches(|c: char| c.is_ascii_digit() || c == '.')
+        .trim_start();
+    let rest = rest.strip_prefix(|c| c == ';' || c == ',')?.trim_start();
+
+    // the `url=` is optional, some pages just give the url by itself
+    let rest = match rest.get(..3) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("url") => {
+            match rest[3..].trim_start().strip_prefix('=') {
+                Some(rest) => rest.trim_start(),
+                None => rest,
+            }
+        }
+        _ => rest,
+    };
+
+    let url = match rest.chars().next() {
+        Some(quote @ '"') | Some(quote @ '\'') => {
+            let rest = &rest[1..];
+            &rest[..rest.find(quote).unwrap_or(rest.len())]
+        }
+        _ => rest,
+    }
+    .trim();
+
+    Some(url).filter(|url| !url.is_empty())
+}
+
+/// Grabs the URLs referenced using `url()` from a stylesheet or `style`
+/// attribute.
+fn parse_css_urls(css: &str) -> Vec<&str> {
+    let mut urls = Vec::new();
+    let mut rest = css;
+
+    while let Some(start) = rest.to_ascii_lowercase().find("url(") {
+        let after = rest[start + 4..].trim_start();
+
+        let (url, remaining) = match after.chars().next() {
+            Some(quote @ '"') | Some(quote @ '\'') => {
+                let inner = &after[1..];
+                match inner.find(quote) {
+                    Some(end) => (&inner[..end], &inner[end + 1..]),
+                    None => break,
+                }
+            }
+            _ => match after.find(')') {
+                Some(end) => (after[..end].trim_end(), &after[end + 1..]),
+                None => break,
+            },
+        };
+
+        if !url.is_empty() {
+            urls.push(url);
+        }
+        rest = remaining;
+    }
+
+    urls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(html: &str, kinds: &[LinkKind]) -> Vec<String> {
+        let base = Url::parse("https://example.com/dir/page").unwrap();
+        find_links(&base, html.as_bytes(), &kinds.iter().cloned().collect())
+            .unwrap()
+            .into_iter()
+            .map(|link| link.unwrap().url.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn srcset() {
+        assert_eq!(parse_srcset("a.png"), vec!["a.png"]);
+        assert_eq!(parse_srcset("a.png 1x, b.png 2x"), vec!["a.png", "b.png"]);
+        assert_eq!(
+            parse_srcset("a.png 100w,b.png 200w"),
+            vec!["a.png", "b.png"]
+        );
+        assert_eq!(parse_srcset("a.png, b.png"), vec!["a.png", "b.png"]);
+        assert_eq!(
+            parse_srcset("/img,crop=1.png 1x, /b.png 2x"),
+            vec!["/img,crop=1.png", "/b.png"]
+        );
+        assert!(parse_srcset("  ,  ").is_empty());
+    }
+
+    #[test]
+    fn meta_refresh() {
+        assert_eq!(parse_meta_refresh("5; url=/next"), Some("/next"));
+        assert_eq!(parse_meta_refresh("0;URL='/quoted'"), Some("/quoted"));
+        assert_eq!(parse_meta_refresh("0; url = \"/spaced\""), Some("/spaced"));
+        assert_eq!(parse_meta_refresh("1.5, /bare"), Some("/bare"));
+        assert_eq!(parse_meta_refresh("5"), None);
+        assert_eq!(parse_meta_refresh("5; url="), None);
+    }
+
+    #[test]
+    fn css_urls() {
+        assert_eq!(
+            parse_css_urls("a { background: url(/a.png) } b { background: URL( \"/b.png\" ) }"),
+            vec!["/a.png", "/b.png"]
+        );
+        assert_eq!(parse_css_urls("url('/c.png')"), vec!["/c.png"]);
+        assert!(parse_css_urls("url()").is_empty());
+        assert!(parse_css_urls("url(/unterminated").is_empty());
+    }
+
+    #[test]
+    fn data_uris() {
+        assert!(is_data_uri("data:image/png;base64,AAAA"));
+        assert!(is_data_uri("  DATA:text/plain,hi"));
+        assert!(!is_data_uri("/data:thing"));
+    }
+
+    #[test]
+    fn content_types_with_links() {
+        assert!(has_links(None));
+        assert!(has_links(Some("text/html")));
+        assert!(has_links(Some("Text/HTML; charset=ISO-8859-1")));
+        assert!(has_links(Some("application/xhtml+xml")));
+        assert!(has_links(Some("application/xml")));
+        assert!(has_links(Some("text/xml")));
+        assert!(!has_links(Some("image/png")));
+        assert!(!has_links(Some("application/pdf")));
+        assert!(!has_links(Some("text/plain")));
+    }
+
+    #[test]
+    fn links_are_resolved() {
+        assert_eq!(
+            urls(
+                r#"<a href="other">a</a><a href="/root#frag">b</a><a href="data:,x">c</a>"#,
+                &[LinkKind::Anchor]
+            ),
+            vec!["https://example.com/dir/other", "https://example.com/root"]
+        );
+    }
+
+    #[test]
+    fn base_is_respected() {
+        assert_eq!(
+            urls(
+                r#"<base href="/base/"><a href="page">a</a>"#,
+                &[LinkKind::Anchor]
+            ),
+            vec!["https://example.com/base/page"]
+        );
+    }
+
+    #[test]
+    fn only_wanted_kinds() {
+        let html = r#"<a href="/a">a</a><img src="/i.png" srcset="/i2.png 2x"><script src="/s.js"></script>"#;
+
+        assert_eq!(
+            urls(html, &[LinkKind::Anchor]),
+            vec!["https://example.com/a"]
+        );
+        assert_eq!(
+            urls(html, &[LinkKind::Image, LinkKind::ImageSrcset]),
+            vec!["https://example.com/i.png", "https://example.com/i2.png"]
+        );
+    }
+}
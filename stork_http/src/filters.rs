@@ -1,11 +1,22 @@
 pub use stork::filters::FilterType;
 
+pub use ipnet::IpNet;
+pub use publicsuffix::List as PublicSuffixList;
+
 use std::borrow::Cow;
+use std::net::IpAddr;
+use std::sync::Arc;
 
 use stork::filters::Filter;
 
-use crate::Link;
+use crate::robots::RobotsCache;
+use crate::{Link, LinkKind, StorkHttpError, Url};
+
+use publicsuffix::Psl;
+use regex::Regex;
+use url::Host;
 
+#[deprecated(note = "use HostFilter to filter links by their host")]
 #[derive(Debug, Clone)]
 pub enum UrlFilterType {
     Path(FilterType),
@@ -22,12 +33,151 @@ impl<'a> DomainFilter<'a> {
 }
 impl<'a> Filter<Link> for DomainFilter<'a> {
     fn matches(&self, link: &Link) -> bool {
-        link.url()
-            .host_str()
-            .map_or(false, |v| v == self.0.as_ref())
+        link.url().host_str().is_some_and(|v| v == self.0.as_ref())
+    }
+}
+
+/// Filters links by their host, see the constructors for the different
+/// ways the host can be matched. Domains given to the filter are
+/// matched case-insensitively and can be given in their unicode or
+/// punycode forms.
+///
+/// ```
+/// # use stork_http::filters::HostFilter;
+/// // matches example.com, www.example.com, a.b.example.com...
+/// let filter = HostFilter::subdomain_of("example.com");
+/// // ...but only when they're being served on port 8080
+/// let filter = HostFilter::subdomain_of("example.com").port(8080);
+/// ```
+#[derive(Debug, Clone)]
+pub struct HostFilter {
+    host: HostMatch,
+    port: Option<u16>,
+}
+
+#[derive(Debug, Clone)]
+enum HostMatch {
+    Any,
+    Exact(String),
+    SubdomainOf(String),
+    RegistrableDomain {
+        domain: String,
+        list: Arc<PublicSuffixList>,
+    },
+    IpRange(IpNet),
+}
+
+impl HostFilter {
+    /// Matches links on any host, for use alongside [HostFilter::port].
+    pub fn any() -> Self {
+        Self::from(HostMatch::Any)
+    }
+
+    /// Matches links on exactly the given host, so `www.example.com`
+    /// won't match `example.com`.
+    pub fn exact(host: &str) -> Self {
+        Self::from(HostMatch::Exact(normalise_host(host)))
+    }
+
+    /// Matches links on the given domain or any of its subdomains.
+    pub fn subdomain_of(domain: &str) -> Self {
+        Self::from(HostMatch::SubdomainOf(normalise_host(domain)))
+    }
+
+    /// Matches links on the same registrable domain (the public suffix
+    /// plus one label, ie. `example.co.uk`) as `host`, so
+    /// `www.example.co.uk` will match `static.example.co.uk` but not
+    /// `other.co.uk`.
+    ///
+    /// The [public suffix list](https://publicsuffix.org/) has to be
+    /// given, and can be parsed from the list's usual format using
+    /// [str::parse]. Hosts without a registrable domain (ie. IP
+    /// addresses or public suffixes themselves) will only match
+    /// themselves.
+    pub fn registrable_domain(host: &str, list: Arc<PublicSuffixList>) -> Self {
+        let host = normalise_host(host);
+        let domain = registrable_domain(&list, &host).unwrap_or(host);
+
+        Self::from(HostMatch::RegistrableDomain { domain, list })
+    }
+
+    /// Matches links whose host is an IP address within the given
+    /// range. Hosts are never resolved, so links using a domain won't
+    /// match.
+    pub fn ip_range(range: IpNet) -> Self {
+        Self::from(HostMatch::IpRange(range))
+    }
+
+    /// Only matches links on the given port, links without an explicit
+    /// port are on the default port for their scheme.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+}
+impl From<HostMatch> for HostFilter {
+    fn from(host: HostMatch) -> Self {
+        Self { host, port: None }
+    }
+}
+impl Filter<Link> for HostFilter {
+    fn matches(&self, link: &Link) -> bool {
+        let url = link.url();
+
+        if let Some(port) = self.port {
+            if url.port_or_known_default() != Some(port) {
+                return false;
+            }
+        }
+
+        let host = match url.host_str() {
+            Some(host) => host,
+            None => return false,
+        };
+
+        match &self.host {
+            HostMatch::Any => true,
+            HostMatch::Exact(expected) => host == expected,
+            // IP addresses don't have subdomains or registrable domains
+            // so can only ever match exactly
+            HostMatch::SubdomainOf(domain) => match url.domain() {
+                Some(host) => host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.ends_with('.')),
+                None => host == domain,
+            },
+            HostMatch::RegistrableDomain { domain, list } => match url.domain() {
+                Some(host) => {
+                    host == domain || registrable_domain(list, host).as_ref() == Some(domain)
+                }
+                None => host == domain,
+            },
+            HostMatch::IpRange(range) => match url.host() {
+                Some(Host::Ipv4(ip)) => range.contains(&IpAddr::V4(ip)),
+                Some(Host::Ipv6(ip)) => range.contains(&IpAddr::V6(ip)),
+                _ => false,
+            },
+        }
     }
 }
 
+/// Converts `host` to the form [Url::host_str] would give it in, so
+/// unicode domains are converted to punycode and everything's
+/// lowercased. Hosts that can't be parsed are just lowercased.
+fn normalise_host(host: &str) -> String {
+    Host::parse(host)
+        .map(|host| host.to_string())
+        .unwrap_or_else(|_| host.to_lowercase())
+}
+
+/// Gets the registrable domain (eTLD+1) of `host` from the public
+/// suffix list.
+fn registrable_domain(list: &PublicSuffixList, host: &str) -> Option<String> {
+    list.domain(host.as_bytes())
+        .and_then(|domain| std::str::from_utf8(domain.as_bytes()).ok())
+        .map(ToString::to_string)
+}
+
 #[derive(Debug, Clone)]
 pub struct SchemeFilter<'a>(Cow<'a, str>);
 impl<'a> SchemeFilter<'a> {
@@ -66,3 +216,388 @@ impl<'a> Filter<Link> for PathFilter<'a> {
         }
     }
 }
+
+/// Filters links by the parameters in their query string.
+///
+/// ```
+/// # use stork_http::filters::QueryFilter;
+/// // matches links without a sessionid
+/// let filter = QueryFilter::exclude("sessionid");
+/// // matches links to the second page
+/// let filter = QueryFilter::include("page").value("2");
+/// ```
+#[derive(Debug, Clone)]
+pub struct QueryFilter<'a> {
+    name: Cow<'a, str>,
+    value: Option<Cow<'a, str>>,
+    exclude: bool,
+}
+impl<'a> QueryFilter<'a> {
+    /// Matches links with the given parameter in their query string.
+    pub fn include<V: Into<Cow<'a, str>>>(name: V) -> Self {
+        Self {
+            name: name.into(),
+            value: None,
+            exclude: false,
+        }
+    }
+
+    /// Matches links without the given parameter in their query string.
+    pub fn exclude<V: Into<Cow<'a, str>>>(name: V) -> Self {
+        Self {
+            name: name.into(),
+            value: None,
+            exclude: true,
+        }
+    }
+
+    /// Only considers the parameter to be present when it has the given
+    /// (decoded) value.
+    pub fn value<V: Into<Cow<'a, str>>>(mut self, value: V) -> Self {
+        self.value = Some(value.into());
+        self
+    }
+}
+impl<'a> Filter<Link> for QueryFilter<'a> {
+    fn matches(&self, link: &Link) -> bool {
+        let found = link.url().query_pairs().any(|(name, value)| {
+            name == self.name.as_ref()
+                && self
+                    .value
+                    .as_ref()
+                    .is_none_or(|expected| value == expected.as_ref())
+        });
+
+        found != self.exclude
+    }
+}
+
+/// Part of a URL for a [RegexFilter] or [GlobFilter] to match against.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UrlComponent {
+    /// The full URL.
+    Url,
+    Host,
+    Path,
+    /// The query string, without the leading `?`.
+    Query,
+    /// The fragment, without the leading `#`.
+    Fragment,
+}
+impl UrlComponent {
+    /// Grabs this component from the URL, if it has one.
+    fn get(self, url: &Url) -> Option<&str> {
+        match self {
+            UrlComponent::Url => Some(url.as_str()),
+            UrlComponent::Host => url.host_str(),
+            UrlComponent::Path => Some(url.path()),
+            UrlComponent::Query => url.query(),
+            UrlComponent::Fragment => url.fragment(),
+        }
+    }
+}
+
+/// Filters links by matching a regex against their URL, or a part of
+/// it. The regex isn't anchored so will match anywhere within the
+/// component unless told otherwise using `^` and `$`.
+///
+/// Links without the given component (ie. no query string) won't match.
+#[derive(Debug, Clone)]
+pub struct RegexFilter {
+    component: UrlComponent,
+    regex: Regex,
+}
+impl RegexFilter {
+    pub fn new(component: UrlComponent, pattern: &str) -> Result<Self, StorkHttpError> {
+        Ok(Self {
+            component,
+            regex: Regex::new(pattern).map_err(|source| StorkHttpError::PatternParseError {
+                pattern: pattern.to_string(),
+                source,
+            })?,
+        })
+    }
+}
+impl Filter<Link> for RegexFilter {
+    fn matches(&self, link: &Link) -> bool {
+        self.component
+            .get(link.url())
+            .is_some_and(|v| self.regex.is_match(v))
+    }
+}
+
+/// Filters links by matching a glob against their URL, or a part of it.
+/// The glob has to match the whole component.
+///
+/// `*` matches anything other than a `/`, `**` matches anything at all
+/// and `?` matches any single character other than a `/`. For example,
+/// `*.example.com` matches the subdomains of example.com (but not
+/// example.com itself) when matched against the host.
+///
+/// Links without the given component (ie. no query string) won't match.
+#[derive(Debug, Clone)]
+pub struct GlobFilter {
+    component: UrlComponent,
+    regex: Regex,
+}
+impl GlobFilter {
+    pub fn new(component: UrlComponent, glob: &str) -> Result<Self, StorkHttpError> {
+        let mut pattern = String::from("^");
+        let mut chars = glob.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    pattern.push_str(".*");
+                }
+                '*' => pattern.push_str("[^/]*"),
+                '?' => pattern.push_str("[^/]"),
+                c => pattern.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+            }
+        }
+        pattern.push('$');
+
+        Ok(Self {
+            component,
+            regex: Regex::new(&pattern).map_err(|source| StorkHttpError::PatternParseError {
+                pattern: glob.to_string(),
+                source,
+            })?,
+        })
+    }
+}
+impl Filter<Link> for GlobFilter {
+    fn matches(&self, link: &Link) -> bool {
+        self.component
+            .get(link.url())
+            .is_some_and(|v| self.regex.is_match(v))
+    }
+}
+
+/// Filters links by the tokens in their `rel` attribute, see
+/// [Link::rel].
+#[derive(Debug, Clone)]
+pub struct RelFilter<'a> {
+    value: Cow<'a, str>,
+    exclude: bool,
+}
+impl<'a> RelFilter<'a> {
+    /// Matches links with the given token in their `rel`.
+    pub fn include<V: Into<Cow<'a, str>>>(value: V) -> Self {
+        Self {
+            value: value.into(),
+            exclude: false,
+        }
+    }
+
+    /// Matches links without the given token in their `rel`.
+    pub fn exclude<V: Into<Cow<'a, str>>>(value: V) -> Self {
+        Self {
+            value: value.into(),
+            exclude: true,
+        }
+    }
+}
+impl<'a> Filter<Link> for RelFilter<'a> {
+    fn matches(&self, link: &Link) -> bool {
+        link.has_rel(self.value.as_ref()) != self.exclude
+    }
+}
+
+/// Filters links by where they were found on the page, see
+/// [Link::kind]. Matches links of any of the given kinds.
+#[derive(Debug, Clone)]
+pub struct KindFilter(Vec<LinkKind>);
+impl KindFilter {
+    pub fn new<I: IntoIterator<Item = LinkKind>>(kinds: I) -> Self {
+        Self(kinds.into_iter().collect())
+    }
+}
+impl Filter<Link> for KindFilter {
+    fn matches(&self, link: &Link) -> bool {
+        link.kind().is_some_and(|kind| self.0.contains(&kind))
+    }
+}
+
+/// Filters out links disallowed by their host's `robots.txt`, created
+/// using [HttpStorkClient::robots_filter](crate::HttpStorkClient::robots_filter).
+///
+/// This filter can only act on hosts whose `robots.txt` the client has
+/// already fetched, which it does before fetching the first page from
+/// any host. Links to a host that hasn't been visited yet will match.
+#[derive(Debug, Clone)]
+pub struct RobotsFilter {
+    cache: Option<Arc<RobotsCache>>,
+    user_agent: String,
+}
+impl RobotsFilter {
+    pub(crate) fn new(cache: Option<Arc<RobotsCache>>, user_agent: String) -> Self {
+        Self { cache, user_agent }
+    }
+}
+impl Filter<Link> for RobotsFilter {
+    fn matches(&self, link: &Link) -> bool {
+        self.cache
+            .as_ref()
+            .and_then(|cache| cache.get(link.url()))
+            .is_none_or(|robots| robots.is_allowed(&self.user_agent, link.url()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(url: &str) -> Link {
+        Link::from(Url::parse(url).unwrap())
+    }
+
+    fn glob(component: UrlComponent, glob: &str, url: &str) -> bool {
+        GlobFilter::new(component, glob)
+            .unwrap()
+            .matches(&link(url))
+    }
+
+    #[test]
+    fn glob_single_star_stays_within_a_segment() {
+        assert!(glob(
+            UrlComponent::Path,
+            "/blog/*",
+            "https://a.com/blog/post"
+        ));
+        assert!(!glob(
+            UrlComponent::Path,
+            "/blog/*",
+            "https://a.com/blog/2020/post"
+        ));
+        assert!(glob(UrlComponent::Host, "*.a.com", "https://www.a.com/"));
+        assert!(!glob(UrlComponent::Host, "*.a.com", "https://a.com/"));
+    }
+
+    #[test]
+    fn glob_double_star_crosses_segments() {
+        assert!(glob(
+            UrlComponent::Path,
+            "/blog/**",
+            "https://a.com/blog/2020/post"
+        ));
+        assert!(glob(
+            UrlComponent::Path,
+            "/**.pdf",
+            "https://a.com/docs/x/file.pdf"
+        ));
+        assert!(!glob(
+            UrlComponent::Path,
+            "/**.pdf",
+            "https://a.com/docs/file.pdfx"
+        ));
+    }
+
+    #[test]
+    fn glob_question_mark_and_escaping() {
+        assert!(glob(UrlComponent::Path, "/page?", "https://a.com/page2"));
+        assert!(!glob(UrlComponent::Path, "/page?", "https://a.com/page/"));
+        // regex metacharacters are matched literally
+        assert!(glob(
+            UrlComponent::Path,
+            "/a.b+(c)",
+            "https://a.com/a.b+(c)"
+        ));
+        assert!(!glob(UrlComponent::Path, "/a.b", "https://a.com/aXb"));
+    }
+
+    #[test]
+    fn glob_must_match_whole_component() {
+        assert!(!glob(
+            UrlComponent::Path,
+            "/blog",
+            "https://a.com/blog/post"
+        ));
+        assert!(!glob(UrlComponent::Query, "a=1", "https://a.com/"));
+        assert!(glob(UrlComponent::Query, "a=*", "https://a.com/?a=1"));
+    }
+
+    #[test]
+    fn regex_matches_anywhere_unless_anchored() {
+        let regex = |component, pattern| RegexFilter::new(component, pattern).unwrap();
+
+        assert!(regex(UrlComponent::Url, "blog").matches(&link("https://a.com/my-blog/")));
+        assert!(!regex(UrlComponent::Path, "^/blog").matches(&link("https://a.com/my-blog/")));
+        assert!(regex(UrlComponent::Path, r"\.pdf$").matches(&link("https://a.com/x/file.pdf")));
+        assert!(!regex(UrlComponent::Path, r"\.pdf$").matches(&link("https://a.com/file.pdfx")));
+        assert!(regex(UrlComponent::Host, r"^www\.").matches(&link("https://www.a.com/")));
+    }
+
+    #[test]
+    fn regex_needs_the_component() {
+        let filter = RegexFilter::new(UrlComponent::Query, ".*").unwrap();
+        assert!(filter.matches(&link("https://a.com/?a=1")));
+        assert!(!filter.matches(&link("https://a.com/")));
+
+        let filter = RegexFilter::new(UrlComponent::Fragment, "^top$").unwrap();
+        assert!(filter.matches(&link("https://a.com/#top")));
+        assert!(!filter.matches(&link("https://a.com/top")));
+    }
+
+    #[test]
+    fn invalid_regex() {
+        assert!(matches!(
+            RegexFilter::new(UrlComponent::Url, "("),
+            Err(StorkHttpError::PatternParseError { .. })
+        ));
+    }
+
+    #[test]
+    fn host_filter() {
+        assert!(HostFilter::exact("a.com").matches(&link("https://A.com/x")));
+        assert!(!HostFilter::exact("a.com").matches(&link("https://www.a.com/x")));
+        assert!(HostFilter::subdomain_of("a.com").matches(&link("https://www.a.com/")));
+        assert!(HostFilter::subdomain_of("a.com").matches(&link("https://a.com/")));
+        assert!(!HostFilter::subdomain_of("a.com").matches(&link("https://aa.com/")));
+        assert!(HostFilter::exact("a.com")
+            .port(443)
+            .matches(&link("https://a.com/")));
+        assert!(!HostFilter::exact("a.com")
+            .port(443)
+            .matches(&link("http://a.com/")));
+        assert!(
+            HostFilter::ip_range("10.0.0.0/8".parse().unwrap()).matches(&link("http://10.1.2.3/"))
+        );
+        assert!(
+            !HostFilter::ip_range("10.0.0.0/8".parse().unwrap()).matches(&link("http://11.1.2.3/"))
+        );
+    }
+
+    #[test]
+    fn query_filter_include() {
+        let filter = QueryFilter::include("page");
+
+        assert!(filter.matches(&link("https://a.com/?page=2")));
+        assert!(filter.matches(&link("https://a.com/?a=1&page")));
+        assert!(!filter.matches(&link("https://a.com/?pages=2")));
+        assert!(!filter.matches(&link("https://a.com/")));
+    }
+
+    #[test]
+    fn query_filter_exclude() {
+        let filter = QueryFilter::exclude("sessionid");
+
+        assert!(filter.matches(&link("https://a.com/?page=2")));
+        assert!(filter.matches(&link("https://a.com/")));
+        assert!(!filter.matches(&link("https://a.com/?page=2&sessionid=abc")));
+    }
+
+    #[test]
+    fn query_filter_value() {
+        let filter = QueryFilter::include("q").value("a b");
+        assert!(filter.matches(&link("https://a.com/?q=a+b")));
+        assert!(filter.matches(&link("https://a.com/?q=x&q=a%20b")));
+        assert!(!filter.matches(&link("https://a.com/?q=a")));
+
+        // excluding a value leaves the parameter's other values alone
+        let filter = QueryFilter::exclude("sort").value("desc");
+        assert!(filter.matches(&link("https://a.com/?sort=asc")));
+        assert!(!filter.matches(&link("https://a.com/?sort=desc")));
+    }
+}
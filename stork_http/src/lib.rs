@@ -6,19 +6,56 @@
 //! or store for later to come back to at another time, all using futures
 //! to allow for parallel processing.
 //!
-//! At this time `rel="nofollow"` is strictly enforced and not possible
-//! to change although this will come in time as more filters are added.
+//! Hosts' `robots.txt` files are respected by default, see
+//! [HttpStorkClientBuilder::respect_robots_txt] and
+//! [filters::RobotsFilter].
+//!
+//! Sitemaps are understood too, yielding the pages they list rather
+//! than any links within them. See [HttpStorkClient::sitemaps] for
+//! seeding a crawl from a site's sitemaps.
+//!
+//! Only `<a href>` links are looked for by default, other kinds of
+//! links (images, scripts, stylesheets and so on) can be looked for
+//! using [HttpStorkClientBuilder::link_kinds] and then filtered using
+//! [filters::KindFilter].
+//!
+//! Redirects are followed (up to
+//! [HttpStorkClientBuilder::max_redirects]) and recorded on the [Page]
+//! kept on the [HttpStorkable] that was storked, see
+//! [Storkable::page](stork::Storkable::page). They can instead be
+//! yielded as links using [HttpStorkClientBuilder::follow_redirects].
+//!
+//! The [Page] also records the response's status, content type, length
+//! and timing, along with any headers asked for using
+//! [HttpStorkClientBuilder::record_header].
+//!
+//! Links with `rel="nofollow"` are skipped by default, this can be
+//! changed using [HttpStorkClientBuilder::include_nofollow] and links
+//! can then be filtered by their `rel` using [filters::RelFilter].
+//!
+//! Requests that time out, lose their connection or get a response
+//! saying the host is struggling are retried with an exponential
+//! backoff, see [RetryPolicy].
+//!
+//! Requests give up if the host goes quiet for too long, see
+//! [HttpStorkClientBuilder::read_timeout], and bodies larger than
+//! [HttpStorkClientBuilder::max_body_size] fail rather than being read
+//! into memory (or can be truncated instead).
+//!
+//! Links are compared using their exact URL by default, so the same
+//! page linked to with tracking parameters or a differently ordered
+//! query string will be storked twice. Set a [Canonicalizer] using
+//! [HttpStorkClientBuilder::canonicalizer] to have them deduplicated.
 //!
 //! Example usage:
 //!
 //! ```
-//! # use stork::FilterSet;
-//! # use failure::err_msg;
-//! # use stork_http::{HttpStorkable, filters::*};
+//! # use stork::{FilterSet, Storked};
+//! # use stork_http::{HttpStorkable, StatusCode, filters::*};
 //! # use futures::StreamExt;
 //! #
 //! # #[tokio::main]
-//! # async fn main() -> failure::Fallible<()> {
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //! // start scanning https://example.com/ for links with the given filters
 //! let stream = HttpStorkable::new("https://example.com/".parse()?)
 //!     .with_filters(
@@ -28,10 +65,18 @@
 //!     )
 //!     .exec();
 //! # futures::pin_mut!(stream); // needed for iteration
+//! // the page itself comes first, along with the response it was served with
+//! match stream.next().await {
+//!     Some(Ok(Storked::Fetched(fetched))) => {
+//!         assert_eq!(fetched.page().unwrap().status(), StatusCode::OK);
+//!     },
+//!     _ => panic!("failed to get example.com")
+//! };
+//!
 //! // get the first link from example.com and ensure its the one we expected
 //! // it to be
 //! let first_link_on_example: HttpStorkable = match stream.next().await {
-//!     Some(Ok(link)) => {
+//!     Some(Ok(Storked::Child(link))) => {
 //!         assert_eq!(link.val().text(), Some("More information...".to_string()));
 //!         assert_eq!(link.val().url().as_str(), "https://www.iana.org/domains/example");
 //!         assert_eq!(link.parent().unwrap().val().url().as_str(), "https://example.com/");
@@ -45,11 +90,12 @@
 //!     .add_filter(PathFilter::new(FilterType::Equals, "/"));
 //! let stream = first_link_on_example
 //!     .with_filters(filters)
-//!     .exec();
+//!     .exec()
+//!     .filter_map(|found| async move { found.ok()?.into_child() });
 //! # futures::pin_mut!(stream); // needed for iteration
 //! // get the first link from the stream and ensure its a link to the homepage
 //! match stream.next().await {
-//!     Some(Ok(link)) => {
+//!     Some(link) => {
 //!         assert_eq!(link.val().url().as_str(), "https://www.iana.org/");
 //!         assert_eq!(link.parent().unwrap().val().url().as_str(), "https://www.iana.org/domains/example");
 //!         assert_eq!(link.parent().unwrap().parent().unwrap().val().url().as_str(), "https://example.com/")
@@ -62,137 +108,191 @@
 //! # }
 //! ```
 
-#![recursion_limit = "256"]
-
-#[macro_use]
-extern crate failure_derive;
+#![recursion_limit = "512"]
 
+mod canonical;
+mod client;
 mod errors;
+mod extract;
 pub mod filters;
+mod limits;
+mod page;
+mod politeness;
+mod retry;
+pub mod robots;
+pub mod sitemap;
 
+pub use canonical::Canonicalizer;
+pub use client::{HttpStorkClient, HttpStorkClientBuilder};
 pub use errors::StorkHttpError;
+pub use page::{Page, Redirect};
+pub use retry::{RetryPolicy, RetryableError};
 pub use url::Url;
 
-use stork::{StorkClient, Storkable};
-
-use std::pin::Pin;
-
-use select::document::Document;
-use select::predicate::{And, Attr, Name, Not};
-
-use async_stream::try_stream;
-
-use failure::Error;
-use failure::ResultExt;
-
-use std::sync::Arc;
+use stork::Storkable;
 
 pub use reqwest::Client as ReqwestClient;
+pub use reqwest::{header, StatusCode};
 use std::hash::{Hash, Hasher};
 
 pub type HttpStorkable = Storkable<Link, HttpStorkClient>;
 
-#[derive(Debug)]
+/// Where on a page a [Link] was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkKind {
+    /// `<a href>`
+    Anchor,
+    /// `<area href>`
+    Area,
+    /// `<link href>`, ie. stylesheets, icons and alternate pages.
+    Link,
+    /// `<iframe src>`
+    Iframe,
+    /// `<img src>`
+    Image,
+    /// Any of the candidates in an `<img srcset>`.
+    ImageSrcset,
+    /// `<script src>`
+    Script,
+    /// `<form action>`
+    FormAction,
+    /// The URL in a `<meta http-equiv="refresh">`.
+    MetaRefresh,
+    /// A `url()` within a `<style>` element or `style` attribute.
+    Css,
+    /// A `<loc>` within a sitemap.
+    Sitemap,
+    /// The `Location` of a redirect, when redirects aren't being
+    /// followed.
+    Redirect,
+}
+impl LinkKind {
+    /// Every kind of link that can be found within HTML.
+    pub const HTML: [LinkKind; 10] = [
+        LinkKind::Anchor,
+        LinkKind::Area,
+        LinkKind::Link,
+        LinkKind::Iframe,
+        LinkKind::Image,
+        LinkKind::ImageSrcset,
+        LinkKind::Script,
+        LinkKind::FormAction,
+        LinkKind::MetaRefresh,
+        LinkKind::Css,
+    ];
+
+    /// Gets the element this kind of link is found on, if any.
+    pub fn element(self) -> Option<&'static str> {
+        match self {
+            LinkKind::Anchor => Some("a"),
+            LinkKind::Area => Some("area"),
+            LinkKind::Link => Some("link"),
+            LinkKind::Iframe => Some("iframe"),
+            LinkKind::Image | LinkKind::ImageSrcset => Some("img"),
+            LinkKind::Script => Some("script"),
+            LinkKind::FormAction => Some("form"),
+            LinkKind::MetaRefresh => Some("meta"),
+            LinkKind::Css | LinkKind::Sitemap | LinkKind::Redirect => None,
+        }
+    }
+
+    /// Gets the attribute this kind of link is read from, if any.
+    pub fn attribute(self) -> Option<&'static str> {
+        match self {
+            LinkKind::Anchor | LinkKind::Area | LinkKind::Link => Some("href"),
+            LinkKind::Iframe | LinkKind::Image | LinkKind::Script => Some("src"),
+            LinkKind::ImageSrcset => Some("srcset"),
+            LinkKind::FormAction => Some("action"),
+            LinkKind::MetaRefresh => Some("content"),
+            LinkKind::Css | LinkKind::Sitemap | LinkKind::Redirect => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Link {
     url: Url,
     text: Option<String>,
+    rel: Vec<String>,
+    kind: Option<LinkKind>,
+    canonical: Option<Url>,
 }
 impl Link {
     pub fn url(&self) -> &Url {
         &self.url
     }
 
+    /// Get the canonical form of this link's URL, which is what links
+    /// are compared and deduplicated by. This is the same as
+    /// [Link::url] unless the link has been canonicalized, see
+    /// [Canonicalizer].
+    pub fn canonical_url(&self) -> &Url {
+        self.canonical.as_ref().unwrap_or(&self.url)
+    }
+
+    /// Canonicalizes this link's URL using the given [Canonicalizer].
+    /// Links found by a client with a [Canonicalizer] set are already
+    /// canonicalized, but the root of a crawl should be too so it's
+    /// recognised if it's linked back to.
+    pub fn canonicalize(mut self, canonicalizer: &Canonicalizer) -> Self {
+        self.canonical = Some(canonicalizer.apply(&self.url));
+        self
+    }
+
     pub fn text(&self) -> Option<String> {
         self.text.clone()
     }
+
+    /// Get the (lowercased) tokens from the `rel` attribute this link
+    /// was found with, ie. `["noopener", "nofollow"]` for
+    /// `rel="noopener nofollow"`.
+    pub fn rel(&self) -> &[String] {
+        &self.rel
+    }
+
+    /// Checks if this link's `rel` attribute contains the given token,
+    /// ignoring case.
+    pub fn has_rel(&self, token: &str) -> bool {
+        self.rel.iter().any(|rel| rel.eq_ignore_ascii_case(token))
+    }
+
+    /// Get where this link was found, or `None` if it wasn't found on a
+    /// page at all (ie. the root of a crawl).
+    pub fn kind(&self) -> Option<LinkKind> {
+        self.kind
+    }
 }
 impl PartialEq for Link {
     fn eq(&self, other: &Self) -> bool {
-        self.url() == other.url()
+        self.canonical_url() == other.canonical_url()
     }
 }
 impl Hash for Link {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.url().hash(state)
+        self.canonical_url().hash(state)
     }
 }
 impl std::str::FromStr for Link {
-    type Err = failure::Error;
+    type Err = StorkHttpError;
 
-    fn from_str(input: &str) -> Result<Link, Error> {
-        Ok(Self {
-            url: Url::parse(input).context(StorkHttpError::UrlParseError)?,
-            text: None,
-        })
+    fn from_str(input: &str) -> Result<Link, StorkHttpError> {
+        Url::parse(input)
+            .map(Link::from)
+            .map_err(|source| StorkHttpError::UrlParseError {
+                input: input.to_string(),
+                base: None,
+                source,
+            })
     }
 }
 impl From<Url> for Link {
     fn from(url: Url) -> Self {
-        Self { url, text: None }
-    }
-}
-
-pub struct HttpStorkClient {
-    client: Arc<reqwest::Client>,
-}
-
-impl HttpStorkClient {
-    pub fn new(client: ReqwestClient) -> Self {
         Self {
-            client: Arc::new(client),
-        }
-    }
-}
-
-impl Default for HttpStorkClient {
-    fn default() -> Self {
-        Self {
-            client: Arc::new(
-                reqwest::Client::builder()
-                    .user_agent(concat!(
-                        env!("CARGO_PKG_NAME"),
-                        "/",
-                        env!("CARGO_PKG_VERSION")
-                    ))
-                    .build()
-                    .unwrap(),
-            ),
+            url,
+            text: None,
+            rel: Vec::new(),
+            kind: None,
+            canonical: None,
         }
     }
 }
-
-impl StorkClient<Link> for HttpStorkClient {
-    fn run(&self, src: &Link) -> Pin<Box<dyn futures::Stream<Item = Result<Link, Error>>>> {
-        let root = src.url.clone();
-        let client = Arc::clone(&self.client);
-
-        Box::pin(try_stream! {
-            // TODO: can we get this to stream into the Document? need some
-            // TODO: compat layer between futures and std::io::Read
-            let doc = client.get(root.clone())
-                .send().await.context(StorkHttpError::HttpError)?
-                .bytes().await.context(StorkHttpError::HttpError)?;
-            let document = Document::from_read(&doc[..]).context(StorkHttpError::HtmlParseError)?;
-
-            for node in document.find(And(Name("a"), Not(Attr("rel", "nofollow")))) {
-                let title = node.text().trim().to_string();
-                let href = node.attr("href");
-
-                if let Some(href) = href {
-                    // if this looks like a relative url append it to the root
-                    let mut href = if href.starts_with('/') || !href.contains("://") {
-                        root.join(href).context(StorkHttpError::UrlParseError)?
-                    } else {
-                        Url::parse(href).context(StorkHttpError::UrlParseError)?
-                    };
-                    href.set_fragment(None);
-
-                    yield Link {
-                        url: href,
-                        text: Some(title).filter(|x| !x.is_empty())
-                    };
-                }
-            }
-        })
-    }
-}
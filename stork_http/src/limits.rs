@@ -0,0 +1,64 @@
+use crate::Url;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Caps the number of requests a [HttpStorkClient](crate::HttpStorkClient)
+/// has in flight, both overall and to any single host.
+#[derive(Debug, Default)]
+pub(crate) struct ConcurrencyLimiter {
+    global: Option<Arc<Semaphore>>,
+    per_host: Option<usize>,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+/// Held for the duration of a request, releasing its slot(s) in the
+/// [ConcurrencyLimiter] when dropped.
+pub(crate) struct Permit {
+    _host: Option<OwnedSemaphorePermit>,
+    _global: Option<OwnedSemaphorePermit>,
+}
+
+impl ConcurrencyLimiter {
+    pub(crate) fn new(global: Option<usize>, per_host: Option<usize>) -> Self {
+        Self {
+            global: global.map(|permits| Arc::new(Semaphore::new(permits.max(1)))),
+            per_host: per_host.map(|permits| permits.max(1)),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits until there's a slot available for a request to the given
+    /// `url`.
+    pub(crate) async fn acquire(&self, url: &Url) -> Permit {
+        let host = match (self.per_host, url.host_str()) {
+            (Some(permits), Some(host)) => Some(Arc::clone(
+                self.hosts
+                    .lock()
+                    .unwrap()
+                    .entry(host.to_string())
+                    .or_insert_with(|| Arc::new(Semaphore::new(permits))),
+            )),
+            _ => None,
+        };
+
+        // grab the host's permit before the global one so we're not
+        // taking up a global slot whilst waiting on a busy host
+        let host = match host {
+            Some(host) => Some(host.acquire_owned().await),
+            None => None,
+        };
+
+        let global = match &self.global {
+            Some(global) => Some(Arc::clone(global).acquire_owned().await),
+            None => None,
+        };
+
+        Permit {
+            _host: host,
+            _global: global,
+        }
+    }
+}
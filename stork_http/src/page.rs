@@ -0,0 +1,146 @@
+//! Details about the page a [Link](crate::Link) pointed to, recorded
+//! once it's been fetched.
+
+use crate::Url;
+
+use reqwest::header::{HeaderMap, HeaderName, CONTENT_LENGTH, CONTENT_TYPE};
+use reqwest::{Response, StatusCode};
+
+use std::time::Duration;
+
+/// A page fetched by [HttpStorkClient](crate::HttpStorkClient), which
+/// can be grabbed from the [HttpStorkable](crate::HttpStorkable) that
+/// pointed to it using [Storkable::page](stork::Storkable::page) once it
+/// has been storked.
+#[derive(Debug, Clone)]
+pub struct Page {
+    pub(crate) url: Url,
+    pub(crate) redirects: Vec<Redirect>,
+    pub(crate) status: StatusCode,
+    pub(crate) headers: HeaderMap,
+    pub(crate) content_type: Option<String>,
+    pub(crate) content_length: Option<u64>,
+    pub(crate) duration: Duration,
+    pub(crate) attempts: usize,
+    pub(crate) truncated: bool,
+}
+
+impl Page {
+    /// Records the details of `response`, keeping hold of only the
+    /// headers in `recorded`. The duration is left for the caller to
+    /// fill in once they're done with the response.
+    pub(crate) fn from_response(
+        response: &Response,
+        redirects: Vec<Redirect>,
+        recorded: &[HeaderName],
+    ) -> Self {
+        let headers = response.headers();
+
+        Self {
+            url: response.url().clone(),
+            redirects,
+            status: response.status(),
+            headers: recorded
+                .iter()
+                .flat_map(|name| {
+                    headers
+                        .get_all(name)
+                        .iter()
+                        .map(move |value| (name.clone(), value.clone()))
+                })
+                .collect(),
+            content_type: headers
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string),
+            content_length: headers
+                .get(CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse().ok()),
+            duration: Duration::default(),
+            attempts: 1,
+            truncated: false,
+        }
+    }
+
+    /// Gets the URL the page was eventually served from, after following
+    /// any redirects.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Gets the redirects that were followed to get to this page, in the
+    /// order they were followed.
+    pub fn redirects(&self) -> &[Redirect] {
+        &self.redirects
+    }
+
+    /// Gets the status the page was served with, which for a redirect
+    /// that wasn't followed is the redirect's status.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Gets the headers recorded from the page's response, which are
+    /// `Last-Modified`, `ETag` and any asked for using
+    /// [HttpStorkClientBuilder::record_header](crate::HttpStorkClientBuilder::record_header).
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Gets the page's `Content-Type`, if it was served with one.
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    /// Gets the length of the page's body, as given by its
+    /// `Content-Length` or, if it didn't have one, as read.
+    pub fn content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+
+    /// Gets how long the page took to fetch, from sending the request to
+    /// reading the last of its body. Time spent waiting on politeness
+    /// constraints or following redirects isn't included.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Gets how many times the page had to be requested before we got
+    /// it, 1 if the first attempt was successful. Retries of any
+    /// redirects followed to get to the page aren't counted.
+    ///
+    /// See [RetryPolicy](crate::RetryPolicy) for when requests are
+    /// retried.
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    /// Checks if the page's body was cut short because it was larger
+    /// than the
+    /// [max body size](crate::HttpStorkClientBuilder::max_body_size), in
+    /// which case only links found before the cut were yielded.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+/// A single hop in a redirect chain.
+#[derive(Debug, Clone)]
+pub struct Redirect {
+    pub(crate) url: Url,
+    pub(crate) status: StatusCode,
+}
+
+impl Redirect {
+    /// Gets the URL that responded with the redirect.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Gets the status the redirect was sent with, ie. `301 Moved
+    /// Permanently`.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+}
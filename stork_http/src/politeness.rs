@@ -0,0 +1,272 @@
+use crate::Url;
+
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Response, StatusCode};
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Keeps a [HttpStorkClient](crate::HttpStorkClient) from hammering any
+/// single host, by rate limiting requests using a token bucket per host,
+/// enforcing a minimum delay between requests to the same host and
+/// backing off from hosts that tell us to slow down.
+#[derive(Debug)]
+pub(crate) struct Throttle {
+    rate_limit: Option<RateLimit>,
+    min_delay: Duration,
+    hosts: Mutex<HashMap<String, HostState>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RateLimit {
+    pub(crate) requests_per_second: f64,
+    pub(crate) burst: f64,
+}
+
+#[derive(Debug)]
+struct HostState {
+    tokens: f64,
+    last_refill: Instant,
+    next_allowed: Instant,
+    min_delay: Duration,
+}
+
+impl Throttle {
+    pub(crate) fn new(rate_limit: Option<RateLimit>, min_delay: Duration) -> Self {
+        Self {
+            rate_limit,
+            min_delay,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn new_host_state(&self, now: Instant) -> HostState {
+        HostState {
+            tokens: self.rate_limit.map_or(0.0, |limit| limit.burst),
+            last_refill: now,
+            next_allowed: now,
+            min_delay: self.min_delay,
+        }
+    }
+
+    /// Waits until we're allowed to send another request to `url`'s
+    /// host, and takes up that slot.
+    pub(crate) async fn wait(&self, url: &Url) {
+        let host = match url.host_str() {
+            Some(host) => host,
+            None => return,
+        };
+
+        loop {
+            let wait = {
+                let now = Instant::now();
+                let mut hosts = self.hosts.lock().unwrap();
+                let state = hosts
+                    .entry(host.to_string())
+                    .or_insert_with(|| self.new_host_state(now));
+
+                // top up the bucket with however many tokens have been
+                // generated since we last looked at it
+                let mut ready_at = state.next_allowed;
+                if let Some(limit) = self.rate_limit {
+                    let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+                    state.tokens =
+                        (state.tokens + elapsed * limit.requests_per_second).min(limit.burst);
+                    state.last_refill = now;
+
+                    if state.tokens < 1.0 {
+                        let refill = (1.0 - state.tokens) / limit.requests_per_second;
+                        ready_at = ready_at.max(now + Duration::from_secs_f64(refill));
+                    }
+                }
+
+                if ready_at <= now {
+                    if self.rate_limit.is_some() {
+                        state.tokens -= 1.0;
+                    }
+                    state.next_allowed = now + state.min_delay;
+                    return;
+                }
+
+                ready_at - now
+            };
+
+            // we don't reserve the slot we're waiting on, since the host
+            // may well ask us to back off even further in the meantime
+            tokio::time::delay_for(wait).await;
+        }
+    }
+
+    /// Stops any more requests from being sent to `url`'s host until
+    /// `delay` has passed.
+    pub(crate) fn back_off(&self, url: &Url, delay: Duration) {
+        let host = match url.host_str() {
+            Some(host) => host,
+            None => return,
+        };
+
+        let now = Instant::now();
+        let mut hosts = self.hosts.lock().unwrap();
+        let state = hosts
+            .entry(host.to_string())
+            .or_insert_with(|| self.new_host_state(now));
+        state.next_allowed = state.next_allowed.max(now + delay);
+    }
+
+    /// Raises the minimum delay between requests to `url`'s host, if
+    /// `delay` is longer than the one already in place. The next request
+    /// to the host will also be held off for `delay`.
+    pub(crate) fn set_min_delay(&self, url: &Url, delay: Duration) {
+        let host = match url.host_str() {
+            Some(host) => host,
+            None => return,
+        };
+
+        let now = Instant::now();
+        let mut hosts = self.hosts.lock().unwrap();
+        let state = hosts
+            .entry(host.to_string())
+            .or_insert_with(|| self.new_host_state(now));
+        state.min_delay = state.min_delay.max(delay);
+        state.next_allowed = state.next_allowed.max(now + delay);
+    }
+}
+
+/// Checks if a response with the given status is the host asking us to
+/// slow down, in which case every request to it should be held off
+/// rather than just the one that got the response.
+pub(crate) fn is_throttled(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE
+}
+
+/// Gets how long the host would like us to wait before retrying, taken
+/// from the response's `Retry-After` header.
+pub(crate) fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_retry_after)
+}
+
+/// Parses the value of a `Retry-After` header, which can either be a
+/// number of seconds or a HTTP date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    httpdate::parse_http_date(value)
+        .ok()
+        // a date in the past means we can go right ahead
+        .map(|date| {
+            date.duration_since(SystemTime::now())
+                .unwrap_or_else(|_| Duration::from_secs(0))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url() -> Url {
+        Url::parse("https://example.com/").unwrap()
+    }
+
+    /// Times how long it takes to be let through to `url` `count` times.
+    async fn time_waits(throttle: &Throttle, url: &Url, count: usize) -> Duration {
+        let started = Instant::now();
+        for _ in 0..count {
+            throttle.wait(url).await;
+        }
+        started.elapsed()
+    }
+
+    #[test]
+    fn parse_retry_after_seconds() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after(" 0 "), Some(Duration::from_secs(0)));
+        assert_eq!(parse_retry_after("-1"), None);
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn parse_retry_after_dates() {
+        let in_a_minute = SystemTime::now() + Duration::from_secs(60);
+        let delay = parse_retry_after(&httpdate::fmt_http_date(in_a_minute)).unwrap();
+        assert!(delay > Duration::from_secs(55) && delay <= Duration::from_secs(60));
+
+        // dates in the past don't hold us up at all
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::from_secs(0))
+        );
+    }
+
+    #[test]
+    fn throttled_statuses() {
+        assert!(is_throttled(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_throttled(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_throttled(StatusCode::INTERNAL_SERVER_ERROR));
+        assert!(!is_throttled(StatusCode::OK));
+    }
+
+    #[tokio::test]
+    async fn unlimited_by_default() {
+        let throttle = Throttle::new(None, Duration::from_secs(0));
+        assert!(time_waits(&throttle, &url(), 100).await < Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn token_bucket() {
+        let throttle = Throttle::new(
+            Some(RateLimit {
+                requests_per_second: 10.0,
+                burst: 3.0,
+            }),
+            Duration::from_secs(0),
+        );
+
+        // the bucket starts off full, so the burst goes straight through
+        assert!(time_waits(&throttle, &url(), 3).await < Duration::from_millis(50));
+
+        // after which we're held to the rate
+        let elapsed = time_waits(&throttle, &url(), 3).await;
+        assert!(elapsed >= Duration::from_millis(250), "took {:?}", elapsed);
+
+        // other hosts have their own bucket
+        let other = Url::parse("https://example.org/").unwrap();
+        assert!(time_waits(&throttle, &other, 3).await < Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn min_delay() {
+        let throttle = Throttle::new(None, Duration::from_millis(100));
+
+        let elapsed = time_waits(&throttle, &url(), 3).await;
+        assert!(elapsed >= Duration::from_millis(200), "took {:?}", elapsed);
+
+        // crawl delays can only ever make things slower
+        throttle.set_min_delay(&url(), Duration::from_millis(10));
+        let elapsed = time_waits(&throttle, &url(), 2).await;
+        assert!(elapsed >= Duration::from_millis(100), "took {:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn back_off() {
+        let throttle = Throttle::new(None, Duration::from_secs(0));
+        throttle.wait(&url()).await;
+
+        throttle.back_off(&url(), Duration::from_millis(200));
+        let elapsed = time_waits(&throttle, &url(), 1).await;
+        assert!(elapsed >= Duration::from_millis(150), "took {:?}", elapsed);
+
+        // backing off from one host leaves the rest alone
+        throttle.back_off(&url(), Duration::from_secs(60));
+        let other = Url::parse("https://example.org/").unwrap();
+        assert!(time_waits(&throttle, &other, 1).await < Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,268 @@
+//! Retrying requests which failed for reasons that might not last, see
+//! [RetryPolicy].
+
+use crate::StorkHttpError;
+
+use rand::Rng;
+
+use reqwest::StatusCode;
+
+use std::time::Duration;
+
+/// Decides which failed requests are retried and how long to wait
+/// before each retry, set on a client using
+/// [HttpStorkClientBuilder::retry_policy](crate::HttpStorkClientBuilder::retry_policy).
+///
+/// By default a request is sent up to 4 times, waiting an exponentially
+/// increasing delay starting at a second between each attempt. Requests
+/// are retried if they time out, can't connect or lose their connection
+/// partway through and if the host responds with a `408`, `429`, `500`,
+/// `502`, `503` or `504`.
+///
+/// A host responding with a `Retry-After` header will have it respected
+/// instead of the exponential delay, and a `429 Too Many Requests` or
+/// `503 Service Unavailable` will hold off every request to the host
+/// rather than just the one being retried.
+///
+/// The number of attempts it took to fetch a page is recorded on its
+/// [Page](crate::Page), see [Page::attempts](crate::Page::attempts).
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: usize,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+    statuses: Vec<StatusCode>,
+    errors: Vec<RetryableError>,
+}
+
+/// The kinds of errors sending a request or reading its response can
+/// run into which a [RetryPolicy] can retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryableError {
+    /// The request timed out.
+    Timeout,
+    /// We couldn't connect to the host.
+    Connect,
+    /// The request couldn't be sent, ie. the connection was reset
+    /// before we got a response.
+    Request,
+    /// The response's body couldn't be read (or decompressed), ie. the
+    /// connection was reset partway through it.
+    Body,
+}
+
+impl RetryableError {
+    /// Every kind of error that can be retried.
+    pub const ALL: [RetryableError; 4] = [
+        RetryableError::Timeout,
+        RetryableError::Connect,
+        RetryableError::Request,
+        RetryableError::Body,
+    ];
+
+    fn matches(self, error: &StorkHttpError) -> bool {
+        let source = match error {
+            StorkHttpError::HttpError { source, .. } => source,
+            StorkHttpError::TimedOut { .. } => return self == RetryableError::Timeout,
+            _ => return false,
+        };
+
+        match self {
+            RetryableError::Timeout => source.is_timeout(),
+            RetryableError::Connect => source.is_connect(),
+            RetryableError::Request => source.is_request(),
+            RetryableError::Body => source.is_body() || source.is_decode(),
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            jitter: true,
+            statuses: vec![
+                StatusCode::REQUEST_TIMEOUT,
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            errors: RetryableError::ALL.to_vec(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a [RetryPolicy] which never retries anything.
+    pub fn none() -> Self {
+        Self::default().max_attempts(1)
+    }
+
+    /// Sets the most times a request will be sent (including the first
+    /// attempt) before giving up, defaults to 4. Set to 1 to disable
+    /// retries.
+    pub fn max_attempts(mut self, max: usize) -> Self {
+        self.max_attempts = max.max(1);
+        self
+    }
+
+    /// Sets how long to wait before the first retry, defaults to a
+    /// second. Each retry after that waits twice as long as the last.
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Sets the longest we'll wait before a retry, defaults to 60
+    /// seconds. The exponential delay is capped at this, whilst a host
+    /// asking us to wait any longer than this using `Retry-After` won't
+    /// be retried at all.
+    pub fn max_backoff(mut self, max: Duration) -> Self {
+        self.max_backoff = max;
+        self
+    }
+
+    /// Sets whether the exponential delay should be randomised, so
+    /// requests that failed at the same time don't all retry at the same
+    /// time too. Defaults to true, in which case each delay is somewhere
+    /// between half and all of what it would otherwise be.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Replaces the response statuses that will be retried.
+    pub fn retry_statuses<I: IntoIterator<Item = StatusCode>>(mut self, statuses: I) -> Self {
+        self.statuses = statuses.into_iter().collect();
+        self
+    }
+
+    /// Replaces the kinds of errors that will be retried, pass
+    /// [RetryableError::ALL] to retry any of them.
+    pub fn retry_errors<I: IntoIterator<Item = RetryableError>>(mut self, errors: I) -> Self {
+        self.errors = errors.into_iter().collect();
+        self
+    }
+
+    /// Checks if a response with the given `status` to the given
+    /// `attempt` (starting at 1) should be retried.
+    pub(crate) fn retries_status(&self, status: StatusCode, attempt: usize) -> bool {
+        attempt < self.max_attempts && self.statuses.contains(&status)
+    }
+
+    /// Checks if a request which failed with `error` on the given
+    /// `attempt` (starting at 1) should be retried.
+    pub(crate) fn retries_error(&self, error: &StorkHttpError, attempt: usize) -> bool {
+        attempt < self.max_attempts && self.errors.iter().any(|kind| kind.matches(error))
+    }
+
+    /// Checks if a host asking us to wait for `delay` is asking too much.
+    pub(crate) fn is_too_long(&self, delay: Duration) -> bool {
+        delay > self.max_backoff
+    }
+
+    /// Gets how long to wait before retrying after the given `attempt`
+    /// (starting at 1) failed.
+    pub(crate) fn backoff(&self, attempt: usize) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(16) as u32;
+        let backoff = self
+            .initial_backoff
+            .checked_mul(1 << exponent)
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff));
+
+        if self.jitter {
+            backoff.mul_f64(rand::thread_rng().gen_range(0.5, 1.0))
+        } else {
+            backoff
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Url;
+
+    fn timed_out() -> StorkHttpError {
+        StorkHttpError::TimedOut {
+            url: Url::parse("https://example.com/").unwrap(),
+            after: Duration::from_secs(30),
+        }
+    }
+
+    #[test]
+    fn backoff_doubles() {
+        let policy = RetryPolicy::default().jitter(false);
+
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(4), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let policy = RetryPolicy::default()
+            .jitter(false)
+            .max_backoff(Duration::from_secs(5));
+
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(4), Duration::from_secs(5));
+        assert_eq!(policy.backoff(1000), Duration::from_secs(5));
+
+        assert!(!policy.is_too_long(Duration::from_secs(5)));
+        assert!(policy.is_too_long(Duration::from_secs(6)));
+    }
+
+    #[test]
+    fn backoff_jitter() {
+        let policy = RetryPolicy::default().initial_backoff(Duration::from_millis(100));
+
+        for attempt in 1..=5 {
+            let full = Duration::from_millis(100 << (attempt - 1));
+            let backoff = policy.backoff(attempt);
+            assert!(backoff >= full / 2 && backoff <= full, "{:?}", backoff);
+        }
+    }
+
+    #[test]
+    fn retries_statuses() {
+        let policy = RetryPolicy::default();
+        assert!(policy.retries_status(StatusCode::SERVICE_UNAVAILABLE, 1));
+        assert!(policy.retries_status(StatusCode::TOO_MANY_REQUESTS, 3));
+        // the fourth attempt was the last
+        assert!(!policy.retries_status(StatusCode::SERVICE_UNAVAILABLE, 4));
+        assert!(!policy.retries_status(StatusCode::NOT_FOUND, 1));
+
+        assert!(!RetryPolicy::none().retries_status(StatusCode::SERVICE_UNAVAILABLE, 1));
+
+        let policy = RetryPolicy::default().retry_statuses(vec![StatusCode::NOT_FOUND]);
+        assert!(policy.retries_status(StatusCode::NOT_FOUND, 1));
+        assert!(!policy.retries_status(StatusCode::SERVICE_UNAVAILABLE, 1));
+    }
+
+    #[test]
+    fn retries_errors() {
+        let policy = RetryPolicy::default();
+        assert!(policy.retries_error(&timed_out(), 1));
+        assert!(!policy.retries_error(&timed_out(), 4));
+        assert!(!RetryPolicy::none().retries_error(&timed_out(), 1));
+
+        // only the kinds of errors asked for are retried
+        let policy = RetryPolicy::default().retry_errors(vec![RetryableError::Connect]);
+        assert!(!policy.retries_error(&timed_out(), 1));
+
+        // and anything else is never worth retrying
+        let disallowed = StorkHttpError::RobotsDisallowed {
+            url: Url::parse("https://example.com/").unwrap(),
+        };
+        assert!(!RetryPolicy::default()
+            .retry_errors(RetryableError::ALL.to_vec())
+            .retries_error(&disallowed, 1));
+    }
+}
@@ -0,0 +1,431 @@
+//! Parsing and evaluation of `robots.txt` files, as described by
+//! [RFC 9309](https://www.rfc-editor.org/rfc/rfc9309).
+//!
+//! [HttpStorkClient](crate::HttpStorkClient) will fetch and respect
+//! `robots.txt` automatically unless told otherwise, see
+//! [HttpStorkClientBuilder::respect_robots_txt](crate::HttpStorkClientBuilder::respect_robots_txt)
+//! and [RobotsFilter](crate::filters::RobotsFilter).
+
+use crate::Url;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+/// A parsed `robots.txt` file.
+#[derive(Debug, Clone, Default)]
+pub struct Robots {
+    groups: Vec<Group>,
+    sitemaps: Vec<Url>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Group {
+    user_agents: Vec<String>,
+    rules: Vec<Rule>,
+    crawl_delay: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+impl Robots {
+    /// Parses a `robots.txt` file, any lines that can't be understood
+    /// are ignored.
+    pub fn parse(input: &str) -> Self {
+        let mut robots = Robots::default();
+        let mut current: Option<Group> = None;
+        // whether the last line we saw was a user-agent line, in which
+        // case another user-agent line adds to the current group rather
+        // than starting a new one
+        let mut in_user_agents = false;
+
+        for line in input.lines() {
+            let line = match line.find('#') {
+                Some(comment) => &line[..comment],
+                None => line,
+            };
+
+            let mut parts = line.splitn(2, ':');
+            let key = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+            let value = match parts.next() {
+                Some(value) => value.trim(),
+                None => continue,
+            };
+
+            match key.as_str() {
+                "user-agent" => {
+                    if !in_user_agents {
+                        robots.groups.extend(current.take());
+                    }
+
+                    current
+                        .get_or_insert_with(Group::default)
+                        .user_agents
+                        .push(value.to_ascii_lowercase());
+                    in_user_agents = true;
+                }
+                "allow" | "disallow" => {
+                    in_user_agents = false;
+
+                    // an empty disallow doesn't disallow anything, and an
+                    // empty allow doesn't mean anything at all
+                    if value.is_empty() {
+                        continue;
+                    }
+
+                    if let Some(group) = current.as_mut() {
+                        group.rules.push(Rule {
+                            allow: key == "allow",
+                            pattern: value.to_string(),
+                        });
+                    }
+                }
+                "crawl-delay" => {
+                    in_user_agents = false;
+
+                    // anything negative or too large to be a duration at all
+                    // is nonsense, so is ignored
+                    let delay = value
+                        .parse::<f64>()
+                        .ok()
+                        .and_then(|delay| Duration::try_from_secs_f64(delay).ok());
+
+                    if let (Some(group), Some(delay)) = (current.as_mut(), delay) {
+                        group.crawl_delay = Some(delay);
+                    }
+                }
+                "sitemap" => {
+                    // sitemaps don't belong to any group
+                    if let Ok(url) = Url::parse(value) {
+                        robots.sitemaps.push(url);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        robots.groups.extend(current);
+        robots
+    }
+
+    /// Creates a [Robots] which allows everything, this is used when a
+    /// host doesn't have a `robots.txt`.
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// Creates a [Robots] which disallows everything, this is used when
+    /// a host's `robots.txt` is unreachable.
+    pub fn disallow_all() -> Self {
+        Self {
+            groups: vec![Group {
+                user_agents: vec!["*".to_string()],
+                rules: vec![Rule {
+                    allow: false,
+                    pattern: "/".to_string(),
+                }],
+                crawl_delay: None,
+            }],
+            sitemaps: Vec::new(),
+        }
+    }
+
+    /// Checks if the given `user_agent` is allowed to crawl `url`.
+    ///
+    /// The most specific (longest) matching rule wins, with allow rules
+    /// winning ties. `/robots.txt` itself is always allowed.
+    pub fn is_allowed(&self, user_agent: &str, url: &Url) -> bool {
+        let mut path = url.path().to_string();
+        if path == "/robots.txt" {
+            return true;
+        }
+        if let Some(query) = url.query() {
+            path.push('?');
+            path.push_str(query);
+        }
+
+        let mut best: Option<&Rule> = None;
+        for rule in self
+            .groups_for(user_agent)
+            .flat_map(|group| group.rules.iter())
+        {
+            if !pattern_matches(&rule.pattern, &path) {
+                continue;
+            }
+
+            best = match best {
+                Some(best)
+                    if best.pattern.len() > rule.pattern.len()
+                        || (best.pattern.len() == rule.pattern.len() && best.allow) =>
+                {
+                    Some(best)
+                }
+                _ => Some(rule),
+            };
+        }
+
+        best.is_none_or(|rule| rule.allow)
+    }
+
+    /// Gets the delay `user_agent` has been asked to leave between
+    /// requests, if any.
+    pub fn crawl_delay(&self, user_agent: &str) -> Option<Duration> {
+        self.groups_for(user_agent)
+            .filter_map(|group| group.crawl_delay)
+            .max()
+    }
+
+    /// Gets the sitemaps declared in this `robots.txt`.
+    pub fn sitemaps(&self) -> &[Url] {
+        &self.sitemaps
+    }
+
+    /// Finds the groups that apply to the given `user_agent`. Groups
+    /// naming its product token take precedence, falling back to any `*`
+    /// groups if there aren't any.
+    fn groups_for<'a>(&'a self, user_agent: &str) -> impl Iterator<Item = &'a Group> {
+        let token = product_token(user_agent);
+        let has_specific = self
+            .groups
+            .iter()
+            .any(|group| group.user_agents.contains(&token));
+        let wanted = if has_specific { token } else { "*".to_string() };
+
+        self.groups
+            .iter()
+            .filter(move |group| group.user_agents.contains(&wanted))
+    }
+}
+
+/// Grabs the product token from a user agent, ie. `stork_http` from
+/// `stork_http/0.0.3`, lowercased for comparison.
+fn product_token(user_agent: &str) -> String {
+    user_agent
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+/// Matches a path against a robots.txt pattern, where `*` matches any
+/// sequence of characters and a trailing `$` anchors the pattern to the
+/// end of the path. Patterns otherwise match any path they're a prefix
+/// of.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let pattern = pattern.as_bytes();
+    let path = path.as_bytes();
+
+    // classic wildcard matching, remembering the last `*` we saw so we
+    // can backtrack to it and have it swallow another character
+    let (mut p, mut s) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    loop {
+        if p == pattern.len() && (!anchored || s == path.len()) {
+            return true;
+        }
+
+        if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, s));
+            p += 1;
+        } else if p < pattern.len() && s < path.len() && pattern[p] == path[s] {
+            p += 1;
+            s += 1;
+        } else if let Some((star, matched)) = backtrack.filter(|(_, matched)| *matched < path.len())
+        {
+            p = star + 1;
+            s = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+}
+
+/// Cache of the [Robots] for each origin a
+/// [HttpStorkClient](crate::HttpStorkClient) has come across.
+#[derive(Debug, Default)]
+pub(crate) struct RobotsCache {
+    resolved: RwLock<HashMap<String, Arc<Robots>>>,
+    fetching: Mutex<HashMap<String, Arc<futures::lock::Mutex<()>>>>,
+}
+
+impl RobotsCache {
+    /// Gets the [Robots] for `url`'s origin, if they've been fetched.
+    pub(crate) fn get(&self, url: &Url) -> Option<Arc<Robots>> {
+        self.resolved
+            .read()
+            .unwrap()
+            .get(&url.origin().ascii_serialization())
+            .cloned()
+    }
+
+    /// Gets the [Robots] for `url`'s origin, calling `fetch` to grab
+    /// them if they haven't been fetched yet. Only one fetch per origin
+    /// will ever be in flight at once.
+    pub(crate) async fn get_or_fetch<F, Fut>(&self, url: &Url, fetch: F) -> Arc<Robots>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Robots>,
+    {
+        if let Some(robots) = self.get(url) {
+            return robots;
+        }
+
+        let origin = url.origin().ascii_serialization();
+        let lock = Arc::clone(
+            self.fetching
+                .lock()
+                .unwrap()
+                .entry(origin.clone())
+                .or_default(),
+        );
+        let _guard = lock.lock().await;
+
+        // someone else may have fetched them whilst we were waiting
+        if let Some(robots) = self.get(url) {
+            return robots;
+        }
+
+        let robots = Arc::new(fetch().await);
+        self.resolved
+            .write()
+            .unwrap()
+            .insert(origin, Arc::clone(&robots));
+
+        robots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(robots: &str, user_agent: &str, path: &str) -> bool {
+        let url = Url::parse("https://example.com")
+            .unwrap()
+            .join(path)
+            .unwrap();
+        Robots::parse(robots).is_allowed(user_agent, &url)
+    }
+
+    #[test]
+    fn pattern_matches_prefixes() {
+        assert!(pattern_matches("/", "/anything"));
+        assert!(pattern_matches("/fish", "/fish.html"));
+        assert!(pattern_matches("/fish", "/fish/salmon"));
+        assert!(!pattern_matches("/fish", "/Fish"));
+        assert!(!pattern_matches("/fish/", "/fish"));
+    }
+
+    #[test]
+    fn pattern_matches_wildcards() {
+        assert!(pattern_matches("/*.php", "/index.php"));
+        assert!(pattern_matches("/*.php", "/folder/file.php?q=1"));
+        assert!(pattern_matches("/fish*", "/fish"));
+        assert!(pattern_matches("/a*b*c", "/aXbYbZc"));
+        assert!(!pattern_matches("/*.php", "/index.html"));
+        assert!(!pattern_matches("/a*b*c", "/aXbY"));
+    }
+
+    #[test]
+    fn pattern_matches_anchored() {
+        assert!(pattern_matches("/*.php$", "/file.php"));
+        assert!(!pattern_matches("/*.php$", "/file.php?q=1"));
+        assert!(!pattern_matches("/*.php$", "/file.php5"));
+        assert!(pattern_matches("/$", "/"));
+        assert!(!pattern_matches("/$", "/page"));
+        // a `*` before the anchor has to backtrack to find the end
+        assert!(pattern_matches("/*a$", "/banana"));
+    }
+
+    #[test]
+    fn longest_match_wins() {
+        let robots = "User-agent: *\nDisallow: /private\nAllow: /private/public\n";
+
+        assert!(!allowed(robots, "bot", "/private/secret"));
+        assert!(allowed(robots, "bot", "/private/public/page"));
+        assert!(allowed(robots, "bot", "/elsewhere"));
+    }
+
+    #[test]
+    fn allow_wins_ties() {
+        let robots = "User-agent: *\nDisallow: /page\nAllow: /page\n";
+        assert!(allowed(robots, "bot", "/page"));
+
+        let robots = "User-agent: *\nAllow: /page\nDisallow: /page\n";
+        assert!(allowed(robots, "bot", "/page"));
+    }
+
+    #[test]
+    fn query_is_matched() {
+        let robots = "User-agent: *\nDisallow: /*?session=\n";
+
+        assert!(!allowed(robots, "bot", "/page?session=1"));
+        assert!(allowed(robots, "bot", "/page?other=1"));
+    }
+
+    #[test]
+    fn specific_user_agent_takes_precedence() {
+        let robots = "User-agent: *\nDisallow: /\n\nUser-agent: stork_http\nDisallow: /private\n";
+
+        assert!(allowed(robots, "stork_http/0.0.3", "/page"));
+        assert!(!allowed(robots, "stork_http/0.0.3", "/private"));
+        assert!(!allowed(robots, "otherbot/1.0", "/page"));
+    }
+
+    #[test]
+    fn consecutive_user_agents_share_a_group() {
+        let robots = "User-agent: a\nUser-agent: b\nDisallow: /\n";
+
+        assert!(!allowed(robots, "a", "/page"));
+        assert!(!allowed(robots, "b", "/page"));
+        assert!(allowed(robots, "c", "/page"));
+    }
+
+    #[test]
+    fn robots_txt_is_always_allowed() {
+        assert!(allowed(
+            "User-agent: *\nDisallow: /\n",
+            "bot",
+            "/robots.txt"
+        ));
+        assert!(!Robots::disallow_all().is_allowed("bot", &Url::parse("https://a.com/x").unwrap()));
+    }
+
+    #[test]
+    fn crawl_delay() {
+        let robots = Robots::parse("User-agent: *\nCrawl-delay: 1.5\n");
+        assert_eq!(robots.crawl_delay("bot"), Some(Duration::from_millis(1500)));
+
+        let robots = Robots::parse("User-agent: *\nDisallow: /\n");
+        assert_eq!(robots.crawl_delay("bot"), None);
+    }
+
+    #[test]
+    fn nonsense_crawl_delay_is_ignored() {
+        for delay in &["-1", "NaN", "inf", "1e20", "soon"] {
+            let robots = Robots::parse(&format!("User-agent: *\nCrawl-delay: {}\n", delay));
+            assert_eq!(robots.crawl_delay("bot"), None, "{}", delay);
+        }
+    }
+
+    #[test]
+    fn sitemaps() {
+        let robots = Robots::parse("Sitemap: https://example.com/sitemap.xml\nSitemap: nonsense\n");
+
+        assert_eq!(
+            robots.sitemaps(),
+            &[Url::parse("https://example.com/sitemap.xml").unwrap()]
+        );
+    }
+}
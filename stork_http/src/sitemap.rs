@@ -0,0 +1,329 @@
+//! Parsing of [sitemaps](https://www.sitemaps.org/protocol.html) and
+//! sitemap indexes, optionally gzipped.
+//!
+//! [HttpStorkClient](crate::HttpStorkClient) will yield the URLs listed
+//! in any sitemap it fetches instead of looking for HTML links, so a
+//! crawl can be seeded from a site's sitemaps by starting from the
+//! [Link](crate::Link)s returned by
+//! [HttpStorkClient::sitemaps](crate::HttpStorkClient::sitemaps).
+
+use crate::Url;
+
+use flate2::read::GzDecoder;
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use std::io::Read;
+
+/// Largest (uncompressed) sitemap we'll read, as defined by the sitemap
+/// protocol. Anything after this is ignored.
+const MAX_SITEMAP_SIZE: u64 = 50 * 1024 * 1024;
+
+/// A parsed sitemap.
+#[derive(Debug, Clone)]
+pub enum Sitemap {
+    /// A `<urlset>` listing the pages of a site.
+    UrlSet(Vec<Url>),
+    /// A `<sitemapindex>` listing further sitemaps.
+    Index(Vec<Url>),
+}
+
+impl Sitemap {
+    /// Attempts to parse the given body as a sitemap or sitemap index,
+    /// decompressing it first if it's gzipped. Returns `None` if the
+    /// body doesn't look like a sitemap at all.
+    ///
+    /// Only the `<loc>` of each `<url>` (or `<sitemap>`, for an index) is
+    /// read, so the `<loc>`s of extensions like `<image:loc>` aren't
+    /// mistaken for pages. `<loc>`s that can't be parsed as absolute URLs
+    /// are skipped.
+    pub fn parse(body: &[u8]) -> Option<Self> {
+        let decompressed;
+        let body = if body.starts_with(&[0x1f, 0x8b]) {
+            let mut buf = Vec::new();
+            GzDecoder::new(body)
+                .take(MAX_SITEMAP_SIZE)
+                .read_to_end(&mut buf)
+                .ok()?;
+            decompressed = buf;
+            &decompressed[..]
+        } else {
+            body
+        };
+
+        let mut reader = Reader::from_reader(body);
+        reader.trim_text(true);
+
+        let mut sitemap: Option<Sitemap> = None;
+        let mut loc: Option<String> = None;
+        // how many elements deep we are, and if the element at depth 2 is
+        // a `<url>` or `<sitemap>`, the namespace prefix its `<loc>` should
+        // have (so an `<image:loc>` within it isn't taken for it)
+        let mut depth = 0;
+        let mut entry_prefix: Option<Vec<u8>> = None;
+        let mut buf = Vec::new();
+
+        loop {
+            match reader.read_event(&mut buf) {
+                Ok(Event::Start(e)) => {
+                    depth += 1;
+
+                    match (&sitemap, depth, e.local_name()) {
+                        (None, _, b"urlset") => sitemap = Some(Sitemap::UrlSet(Vec::new())),
+                        (None, _, b"sitemapindex") => sitemap = Some(Sitemap::Index(Vec::new())),
+                        // the root element has to be one of the two above
+                        // for this to be a sitemap
+                        (None, _, _) => return None,
+                        (Some(sitemap), 2, name) => {
+                            entry_prefix = Some(prefix(e.name(), name).to_vec())
+                                .filter(|_| name == sitemap.entry_name());
+                        }
+                        (Some(_), 3, b"loc")
+                            if entry_prefix.as_deref() == Some(prefix(e.name(), b"loc")) =>
+                        {
+                            loc = Some(String::new())
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(Event::Text(e)) | Ok(Event::CData(e)) => {
+                    if let Some(loc) = loc.as_mut() {
+                        loc.push_str(&e.unescape_and_decode(&reader).ok()?);
+                    }
+                }
+                Ok(Event::End(_)) => {
+                    // we only ever start a loc for the one we're after, so
+                    // it's this one that's ending
+                    if depth == 3 {
+                        let url = loc.take().and_then(|loc| Url::parse(loc.trim()).ok());
+
+                        if let (Some(sitemap), Some(url)) = (sitemap.as_mut(), url) {
+                            sitemap.urls_mut().push(url);
+                        }
+                    }
+
+                    depth -= 1;
+                }
+                // hand back whatever we managed to get out of a
+                // truncated or malformed sitemap
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+
+            buf.clear();
+        }
+
+        sitemap
+    }
+
+    /// Gets the URLs listed in this sitemap, which are pages for a
+    /// [Sitemap::UrlSet] or more sitemaps for a [Sitemap::Index].
+    pub fn urls(&self) -> &[Url] {
+        match self {
+            Sitemap::UrlSet(urls) | Sitemap::Index(urls) => urls,
+        }
+    }
+
+    /// Takes the URLs listed in this sitemap, see [Sitemap::urls].
+    pub fn into_urls(self) -> Vec<Url> {
+        match self {
+            Sitemap::UrlSet(urls) | Sitemap::Index(urls) => urls,
+        }
+    }
+
+    /// Gets the name of the elements listing each URL in this sitemap.
+    fn entry_name(&self) -> &'static [u8] {
+        match self {
+            Sitemap::UrlSet(_) => b"url",
+            Sitemap::Index(_) => b"sitemap",
+        }
+    }
+
+    fn urls_mut(&mut self) -> &mut Vec<Url> {
+        match self {
+            Sitemap::UrlSet(urls) | Sitemap::Index(urls) => urls,
+        }
+    }
+}
+
+/// Gets the namespace prefix of an element's `name`, including the
+/// `:`, given its `local_name`.
+fn prefix<'a>(name: &'a [u8], local_name: &[u8]) -> &'a [u8] {
+    &name[..name.len() - local_name.len()]
+}
+
+/// Gets the most bytes of a response's body we should read, given the
+/// client's `max` and whether the response could be a sitemap going by
+/// its `url` or `Content-Type` (ie. it's XML or gzipped). Sitemaps are
+/// allowed to be [MAX_SITEMAP_SIZE], however small `max` is.
+pub(crate) fn max_body_size(
+    url: &Url,
+    content_type: Option<&str>,
+    max: Option<usize>,
+) -> Option<usize> {
+    let mime = content_type
+        .and_then(|content_type| content_type.split(';').next())
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let path = url.path().to_ascii_lowercase();
+
+    let may_be_sitemap = mime.ends_with("/xml")
+        || mime == "application/gzip"
+        || mime == "application/x-gzip"
+        || path.ends_with(".xml")
+        || path.ends_with(".xml.gz");
+
+    match max {
+        Some(max) if may_be_sitemap => Some(max.max(MAX_SITEMAP_SIZE as usize)),
+        max => max,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use std::io::Write;
+
+    fn urls(sitemap: &Sitemap) -> Vec<&str> {
+        sitemap.urls().iter().map(Url::as_str).collect()
+    }
+
+    #[test]
+    fn url_set() {
+        let sitemap = Sitemap::parse(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+            <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <url><loc>https://example.com/</loc><lastmod>2020-01-01</lastmod></url>
+                <url><loc> https://example.com/a?b=1&amp;c=2 </loc></url>
+                <url><loc>not a url</loc></url>
+            </urlset>"#,
+        )
+        .unwrap();
+
+        assert!(matches!(sitemap, Sitemap::UrlSet(_)));
+        assert_eq!(
+            urls(&sitemap),
+            vec!["https://example.com/", "https://example.com/a?b=1&c=2"]
+        );
+    }
+
+    #[test]
+    fn extension_locs_are_ignored() {
+        let sitemap = Sitemap::parse(
+            br#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"
+                    xmlns:image="http://www.google.com/schemas/sitemap-image/1.1">
+                <url>
+                    <loc>https://example.com/page</loc>
+                    <image:image><image:loc>https://example.com/photo.jpg</image:loc></image:image>
+                </url>
+                <url><image:loc>https://example.com/lonely.jpg</image:loc></url>
+            </urlset>"#,
+        )
+        .unwrap();
+
+        assert_eq!(urls(&sitemap), vec!["https://example.com/page"]);
+    }
+
+    #[test]
+    fn prefixed_sitemap_namespace() {
+        let sitemap = Sitemap::parse(
+            br#"<sm:urlset xmlns:sm="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <sm:url><sm:loc>https://example.com/page</sm:loc></sm:url>
+            </sm:urlset>"#,
+        )
+        .unwrap();
+
+        assert_eq!(urls(&sitemap), vec!["https://example.com/page"]);
+    }
+
+    #[test]
+    fn index() {
+        let sitemap = Sitemap::parse(
+            br#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <sitemap><loc><![CDATA[https://example.com/sitemap-1.xml]]></loc></sitemap>
+            </sitemapindex>"#,
+        )
+        .unwrap();
+
+        assert!(matches!(sitemap, Sitemap::Index(_)));
+        assert_eq!(urls(&sitemap), vec!["https://example.com/sitemap-1.xml"]);
+    }
+
+    #[test]
+    fn gzipped() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(b"<urlset><url><loc>https://example.com/gz</loc></url></urlset>")
+            .unwrap();
+
+        let sitemap = Sitemap::parse(&encoder.finish().unwrap()).unwrap();
+        assert_eq!(urls(&sitemap), vec!["https://example.com/gz"]);
+    }
+
+    #[test]
+    fn truncated() {
+        let sitemap =
+            Sitemap::parse(b"<urlset><url><loc>https://example.com/a</loc></url><url><lo").unwrap();
+        assert_eq!(urls(&sitemap), vec!["https://example.com/a"]);
+    }
+
+    #[test]
+    fn not_a_sitemap() {
+        assert!(Sitemap::parse(b"<html><a href=\"/\">hi</a></html>").is_none());
+        assert!(Sitemap::parse(b"just some text").is_none());
+    }
+
+    #[test]
+    fn sitemaps_can_be_larger_than_other_bodies() {
+        let url = |path| {
+            Url::parse("https://example.com/")
+                .unwrap()
+                .join(path)
+                .unwrap()
+        };
+        let max = MAX_SITEMAP_SIZE as usize;
+
+        assert_eq!(
+            max_body_size(&url("/"), Some("text/html"), Some(10)),
+            Some(10)
+        );
+        assert_eq!(max_body_size(&url("/"), None, Some(10)), Some(10));
+        assert_eq!(
+            max_body_size(&url("/"), Some("application/xml; charset=UTF-8"), Some(10)),
+            Some(max)
+        );
+        assert_eq!(
+            max_body_size(&url("/"), Some("text/xml"), Some(10)),
+            Some(max)
+        );
+        assert_eq!(
+            max_body_size(&url("/"), Some("application/x-gzip"), Some(10)),
+            Some(max)
+        );
+        assert_eq!(
+            max_body_size(&url("/sitemap.xml"), None, Some(10)),
+            Some(max)
+        );
+        assert_eq!(
+            max_body_size(
+                &url("/Sitemap.XML.gz"),
+                Some("application/octet-stream"),
+                Some(10)
+            ),
+            Some(max)
+        );
+
+        // a larger limit, or none at all, is left alone
+        assert_eq!(
+            max_body_size(&url("/sitemap.xml"), None, Some(max * 2)),
+            Some(max * 2)
+        );
+        assert_eq!(max_body_size(&url("/sitemap.xml"), None, None), None);
+    }
+}
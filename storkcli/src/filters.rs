@@ -0,0 +1,86 @@
+//! Builds up the [FilterSet] links are filtered by from the command line
+//! flags, using the filters from [stork_http::filters].
+
+use stork::filters::{AnyOf, Filter, Not};
+use stork::FilterSet;
+use stork_http::filters::*;
+use stork_http::Link;
+
+use crate::Args;
+
+use stork_http::StorkHttpError;
+
+/// Adds the filters given on the command line to `filters`, failing if
+/// any of the regexes are invalid.
+///
+/// Each flag can be given several times, in which case a link only has
+/// to match one of them, but has to satisfy every different flag given.
+pub fn from_args(
+    args: &Args,
+    mut filters: FilterSet<Link>,
+) -> Result<FilterSet<Link>, StorkHttpError> {
+    let domains = |domains: &[String]| {
+        domains
+            .iter()
+            .map(|domain| DomainFilter::new(domain.clone()))
+            .collect::<Vec<_>>()
+    };
+    let regexes = |regexes: &[String]| {
+        regexes
+            .iter()
+            .map(|regex| RegexFilter::new(UrlComponent::Url, regex))
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    let schemes = args
+        .scheme
+        .iter()
+        .map(|scheme| SchemeFilter::new(scheme.clone()));
+
+    let paths = |kind: FilterType, paths: &[String]| {
+        paths
+            .iter()
+            .map(|path| PathFilter::new(kind.clone(), path.clone()))
+            .collect::<Vec<_>>()
+    };
+
+    if let Some(filter) = any_of(domains(&args.include_domain)) {
+        filters = filters.add_filter(filter);
+    }
+    if let Some(filter) = any_of(domains(&args.exclude_domain)) {
+        filters = filters.add_filter(Not(filter));
+    }
+    if let Some(filter) = any_of(schemes) {
+        filters = filters.add_filter(filter);
+    }
+    if let Some(filter) = any_of(paths(FilterType::StartsWith, &args.path_prefix)) {
+        filters = filters.add_filter(filter);
+    }
+    if let Some(filter) = any_of(paths(FilterType::EndsWith, &args.path_suffix)) {
+        filters = filters.add_filter(filter);
+    }
+    if let Some(filter) = any_of(paths(FilterType::Contains, &args.path_contains)) {
+        filters = filters.add_filter(filter);
+    }
+    if let Some(filter) = any_of(regexes(&args.include_regex)?) {
+        filters = filters.add_filter(filter);
+    }
+    if let Some(filter) = any_of(regexes(&args.exclude_regex)?) {
+        filters = filters.add_filter(Not(filter));
+    }
+
+    Ok(filters)
+}
+
+/// Combines the given filters into an [AnyOf], or `None` if there
+/// weren't any given.
+fn any_of<F, I>(filters: I) -> Option<AnyOf<Link>>
+where
+    F: Filter<Link> + 'static,
+    I: IntoIterator<Item = F>,
+{
+    let mut filters = filters.into_iter().peekable();
+    filters.peek()?;
+
+    Some(filters.fold(AnyOf::default(), AnyOf::add_filter))
+}
@@ -1,11 +1,26 @@
-use std::hash::{Hash, Hasher};
+mod filters;
+mod output;
+mod sitemap;
 
-use futures::{pin_mut, StreamExt};
+use output::{Format, Printer};
 
-use failure::Fallible;
+use futures::{future, pin_mut, stream, Stream, StreamExt};
 
-use stork::FilterSet;
-use stork_http::{filters::*, HttpStorkable, Link};
+use anyhow::bail;
+
+use stork::filters::Filter;
+use stork::{Crawler, FilterSet, StorkError, Storked};
+use stork_http::filters::DomainFilter;
+use stork_http::header::LAST_MODIFIED;
+use stork_http::{HttpStorkClient, HttpStorkable, Link, Page, StorkHttpError};
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::PathBuf;
+
+/// Something found whilst crawling, or the error yielded in its place.
+type Found = Result<Storked<Link, HttpStorkClient>, StorkError<StorkHttpError>>;
 
 #[derive(argh::FromArgs)]
 /// Link hunter with a little bit of magic.
@@ -17,84 +32,369 @@ struct Args {
     max_depth: Option<usize>,
 
     #[argh(switch, short = 'o')]
-    /// only grab links from the same origin, useful for creating
-    /// sitemaps
+    /// only grab links from the same origin, this is always the case
+    /// when writing a sitemap.
     same_origin: bool,
 
+    #[argh(option, default = "1")]
+    /// how many pages should be fetched at once, defaults to 1.
+    concurrency: usize,
+
+    #[argh(option)]
+    /// caps the number of pages fetched at once from any single host,
+    /// leave unspecified for no limit beyond `--concurrency`.
+    max_per_host: Option<usize>,
+
+    #[argh(switch)]
+    /// check every link found is reachable (including links to other
+    /// sites, which won't be crawled) rather than printing them,
+    /// reporting any broken links and exiting with an error if there
+    /// were any. Links that aren't http(s) are skipped.
+    check: bool,
+
+    #[argh(option)]
+    /// write the pages found on the origin out as a sitemap to the
+    /// given path rather than printing them. Sitemaps with more than
+    /// 50,000 pages will be split up and listed in a sitemap index.
+    sitemap: Option<PathBuf>,
+
+    #[argh(option)]
+    /// the URL the sitemaps written using `--sitemap` will be served
+    /// from, used to list them in a sitemap index. Defaults to the root
+    /// of the site.
+    sitemap_base: Option<stork_http::Url>,
+
+    #[argh(switch)]
+    /// gzip the sitemaps written using `--sitemap`, adding `.gz` to the
+    /// path if it isn't there already.
+    gzip: bool,
+
+    #[argh(option)]
+    /// only grab links on the given domain, can be given several times.
+    include_domain: Vec<String>,
+
+    #[argh(option)]
+    /// skip links on the given domain, can be given several times.
+    exclude_domain: Vec<String>,
+
+    #[argh(option)]
+    /// only grab links using the given scheme (ie. https), can be
+    /// given several times.
+    scheme: Vec<String>,
+
+    #[argh(option)]
+    /// only grab links whose path starts with the given value, can be
+    /// given several times.
+    path_prefix: Vec<String>,
+
+    #[argh(option)]
+    /// only grab links whose path ends with the given value, can be
+    /// given several times.
+    path_suffix: Vec<String>,
+
+    #[argh(option)]
+    /// only grab links whose path contains the given value, can be
+    /// given several times.
+    path_contains: Vec<String>,
+
+    #[argh(option)]
+    /// only grab links whose full URL matches the given regex, can be
+    /// given several times.
+    include_regex: Vec<String>,
+
+    #[argh(option)]
+    /// skip links whose full URL matches the given regex, can be given
+    /// several times.
+    exclude_regex: Vec<String>,
+
+    #[argh(option, default = "Format::Text")]
+    /// the format to print links in, one of text (the default), jsonl,
+    /// csv or dot. Links printed as jsonl or csv are printed once their
+    /// status is known.
+    format: Format,
+
     #[argh(positional)]
     url: Link,
 }
 
-fn make_tuple_fn(
-    depth: usize,
-) -> impl Fn(failure::Fallible<HttpStorkable>) -> (Fallible<HttpStorkable>, usize) {
-    move |v| (v, depth)
-}
-
 #[tokio::main]
-async fn main() -> failure::Fallible<()> {
+async fn main() -> anyhow::Result<()> {
     let args: Args = argh::from_env();
-    let url = args.url;
+    let url = args.url.clone();
 
-    let mut filters = FilterSet::default();
-    if args.same_origin {
+    if args.check && args.sitemap.is_some() {
+        bail!("--check and --sitemap can't be used together");
+    }
+
+    let mut filters = filters::from_args(&args, FilterSet::default())?;
+    // a sitemap can only list pages from its own site
+    if args.same_origin || args.sitemap.is_some() {
         filters = filters.add_filter(DomainFilter::new(url.url().host().unwrap().to_string()));
     }
 
-    let queue = futures::stream::SelectAll::new();
-    pin_mut!(queue);
+    let mut client = HttpStorkClient::builder();
+    if let Some(max_per_host) = args.max_per_host {
+        client = client.max_concurrent_requests_per_host(max_per_host);
+    }
+
+    let client = client.build();
+    let root = HttpStorkable::new(url.clone())
+        .with_client(client.clone())
+        .with_filters(filters);
+
+    // links found on the origin are at a depth of 1 to the crawler but
+    // we count them as being at 0
+    let max_depth = args.max_depth.map(|max_depth| max_depth + 1);
+
+    let mut crawler = Crawler::new(root.clone()).concurrency(args.concurrency);
+    if let Some(max_depth) = max_depth {
+        crawler = crawler.max_depth(max_depth);
+    }
 
-    // push the initial Storkable onto the queue
-    queue.push(Box::pin(
-        HttpStorkable::new(url)
-            .with_filters(filters)
-            .exec()
-            .map(make_tuple_fn(0)),
-    ));
+    if args.check {
+        // only crawl the site we were pointed at, but check every link
+        // on it
+        let follow = DomainFilter::new(url.url().host().unwrap().to_string());
+        crawler = crawler.follow_filters(FilterSet::default().add_filter(follow.clone()));
 
-    let mut seen = Vec::new();
+        // the same as the crawler decides which links to stork
+        let followed = |link: &HttpStorkable| {
+            max_depth.is_none_or(|max| link.depth() < max) && follow.matches(link.val())
+        };
 
-    loop {
-        let value = queue.next().await;
+        let broken = check_links(root, crawler.crawl(), client, args.concurrency, followed).await;
 
-        if value.is_none() {
-            break;
+        if broken > 0 {
+            eprintln!("Found {} broken link(s)", broken);
+            std::process::exit(1);
         }
 
-        let (link, depth) = value.unwrap();
+        return Ok(());
+    }
 
-        if let Err(e) = link {
-            eprintln!("Failed to grab a link: {}", e);
-            continue;
+    if let Some(path) = &args.sitemap {
+        let base = match &args.sitemap_base {
+            Some(base) => base.clone(),
+            None => url.url().join("/")?,
+        };
+
+        let entries = sitemap_entries(crawler.crawl(), url.url(), max_depth).await;
+        let (path, written) = sitemap::write(path, &base, &entries, args.gzip)?;
+        eprintln!(
+            "Wrote {} page(s) to {} sitemap(s) at {}",
+            entries.len(),
+            written,
+            path.display()
+        );
+
+        return Ok(());
+    }
+
+    let stdout = std::io::stdout();
+    let mut printer = Printer::new(args.format, stdout.lock(), &root)?;
+
+    let found = crawler.crawl();
+    pin_mut!(found);
+
+    while let Some(found) = found.next().await {
+        match found {
+            Ok(found) => printer.found(found)?,
+            Err(e) => eprintln!("Failed to grab a link: {}", describe(&e)),
         }
+    }
+
+    printer.finish()
+}
+
+/// Builds the sitemap entries for the pages fetched during the crawl,
+/// skipping any which we know to be broken and listing the pages
+/// redirected to rather than the redirects themselves. Errors are printed
+/// out as they happen.
+///
+/// Links that weren't fetched because they were past the crawl's
+/// `max_depth` are listed as they are.
+async fn sitemap_entries<S>(
+    found: S,
+    origin: &stork_http::Url,
+    max_depth: Option<usize>,
+) -> Vec<sitemap::Entry>
+where
+    S: Stream<Item = Found>,
+{
+    pin_mut!(found);
 
-        let link = link.unwrap();
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
 
-        // TODO: see if we can do this in a filter before we even make
-        // TODO: it into the synchronous print loop
-        let hash = {
-            let mut hash = twox_hash::XxHash64::default();
-            link.val().hash(&mut hash);
-            hash.finish()
+    while let Some(found) = found.next().await {
+        let (loc, lastmod) = match found {
+            Ok(Storked::Fetched(fetched)) => match fetched.page() {
+                Some(page) if page.status().is_success() => {
+                    (page.url().clone(), last_modified(page))
+                }
+                _ => continue,
+            },
+            Ok(Storked::Child(link)) if max_depth.is_some_and(|max| link.depth() >= max) => {
+                (link.val().url().clone(), None)
+            }
+            Ok(_) => continue,
+            Err(e) => {
+                eprintln!("Failed to grab a link: {}", describe(&e));
+                continue;
+            }
         };
-        if seen.contains(&hash) {
+
+        // a redirect could have taken us off to another site, and the
+        // domain filter doesn't care about schemes or ports
+        if loc.origin() != origin.origin() {
             continue;
-        } else {
-            seen.push(hash);
         }
 
-        println!("{}", link.val().url());
+        if seen.insert(loc.clone()) {
+            entries.push(sitemap::Entry { loc, lastmod });
+        }
+    }
+
+    entries
+}
+
+/// Gets when `page` was last modified from its `Last-Modified` header, in
+/// the format sitemaps expect.
+fn last_modified(page: &Page) -> Option<String> {
+    page.headers()
+        .get(LAST_MODIFIED)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| chrono::DateTime::parse_from_rfc2822(v).ok())
+        .map(|v| v.with_timezone(&chrono::Utc).to_rfc3339())
+}
 
-        if let Some(max_depth) = args.max_depth {
-            if depth >= max_depth {
+/// Checks each of the links found during the crawl, printing out any
+/// that are broken alongside where they were found. Returns the number of
+/// broken links.
+///
+/// The pages the crawler fetched, starting from `root`, are judged by the
+/// response they were fetched with. Links it isn't going to fetch, as
+/// decided by `followed`, are checked using [HttpStorkClient::check], as
+/// are any it couldn't fetch. Links that aren't http(s) can't be checked
+/// and are skipped.
+async fn check_links<S, F>(
+    root: HttpStorkable,
+    found: S,
+    client: HttpStorkClient,
+    concurrency: usize,
+    followed: F,
+) -> usize
+where
+    S: Stream<Item = Found>,
+    F: Fn(&HttpStorkable) -> bool,
+{
+    // links being storked by the crawler, waiting on it to fetch them
+    let pending = RefCell::new(HashMap::new());
+    pending.borrow_mut().insert(root.val().url().clone(), root);
+
+    let found = found
+        .filter_map(|found| {
+            let found = match found {
+                Ok(Storked::Fetched(fetched)) => {
+                    pending.borrow_mut().remove(fetched.val().url());
+
+                    let page = fetched.page().cloned();
+                    Some((fetched, page))
+                }
+                Ok(Storked::Child(link)) => {
+                    if !matches!(link.val().url().scheme(), "http" | "https") {
+                        eprintln!(
+                            "Skipped {}: only http(s) links can be checked",
+                            link.val().url()
+                        );
+                        None
+                    } else if followed(&link) {
+                        pending.borrow_mut().insert(link.val().url().clone(), link);
+                        None
+                    } else {
+                        Some((link, None))
+                    }
+                }
+                // these have already been checked wherever they were
+                // first found
+                Ok(Storked::Seen(_)) => None,
+                // errors from the crawl itself are for pages that'll be
+                // left pending and checked at the end
+                Err(_) => None,
+            };
+
+            future::ready(found)
+        })
+        // anything still pending once the crawl's finished couldn't be
+        // fetched
+        .chain(
+            stream::once(async {
+                stream::iter(pending.take().into_values().map(|link| (link, None)))
+            })
+            .flatten(),
+        );
+
+    let checks = found
+        .map(|(link, page)| {
+            let client = client.clone();
+
+            async move {
+                let result = match page {
+                    Some(page) => Ok(page),
+                    None => client.check(link.val().url()).await,
+                };
+
+                (link, result)
+            }
+        })
+        .buffer_unordered(concurrency);
+    pin_mut!(checks);
+
+    let mut broken = 0;
+
+    while let Some((link, result)) = checks.next().await {
+        let problem = match result {
+            Ok(page) if page.status().is_client_error() || page.status().is_server_error() => {
+                page.status().to_string()
+            }
+            Ok(_) => continue,
+            Err(e @ StorkHttpError::RobotsDisallowed { .. }) => {
+                eprintln!("Skipped {}: {}", link.val().url(), e);
                 continue;
             }
+            Err(e) => describe(&e),
+        };
+
+        broken += 1;
+        println!("{} ({})", link.val().url(), problem);
+
+        if let Some(parent) = link.parent() {
+            match link.val().text() {
+                Some(text) => println!("    linked from {} as {:?}", parent.val().url(), text),
+                None => println!("    linked from {}", parent.val().url()),
+            }
+        }
+    }
+
+    broken
+}
+
+/// Describes `e` along with everything that caused it, ie. `failed to
+/// send http request to https://example.com/: error trying to connect`.
+fn describe(e: &dyn Error) -> String {
+    let mut description = e.to_string();
+
+    let mut source = e.source();
+    while let Some(e) = source {
+        // some errors already include their cause in their own message
+        let message = e.to_string();
+        if !description.contains(&message) {
+            description.push_str(": ");
+            description.push_str(&message);
         }
 
-        // add children of this storkable to the front of the queue with
-        // 1 depth added on
-        queue.push(Box::pin(link.exec().map(make_tuple_fn(depth + 1))));
+        source = e.source();
     }
 
-    Ok(())
+    description
 }
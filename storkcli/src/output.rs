@@ -0,0 +1,191 @@
+//! Formats the links found during a crawl can be printed in.
+
+use stork::Storked;
+use stork_http::{HttpStorkClient, HttpStorkable, Link, Url};
+
+use anyhow::{anyhow, Error, Result};
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::str::FromStr;
+
+/// Format to print the links found during a crawl in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// One URL per line, printed as soon as each link is found.
+    Text,
+    /// One JSON object per line.
+    Jsonl,
+    /// CSV with a header row.
+    Csv,
+    /// A Graphviz graph of which pages link to which.
+    Dot,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Error> {
+        match input {
+            "text" => Ok(Format::Text),
+            "jsonl" => Ok(Format::Jsonl),
+            "csv" => Ok(Format::Csv),
+            "dot" => Ok(Format::Dot),
+            _ => Err(anyhow!("expected one of text, jsonl, csv or dot")),
+        }
+    }
+}
+
+/// Prints the links found during a crawl in a [Format] as they're
+/// found.
+///
+/// The status of a link is only known once the crawler has fetched it,
+/// so for formats that include it each link is held back until then.
+/// Links that weren't fetched are printed without a status once the
+/// crawl has finished.
+///
+/// The graph drawn for [Format::Dot] includes links to pages that had
+/// already been found elsewhere, not just the ones that were new.
+pub struct Printer<W: Write> {
+    format: Format,
+    out: Out<W>,
+    /// Links waiting on their status, keyed by the order they were found
+    /// in so they're printed in that order.
+    pending: BTreeMap<usize, HttpStorkable>,
+    /// Where each of the links in `pending` is, by URL.
+    pending_urls: HashMap<Url, usize>,
+    found: usize,
+}
+
+enum Out<W: Write> {
+    Plain(W),
+    Csv(Box<csv::Writer<W>>),
+}
+
+impl<W: Write> Printer<W> {
+    /// Starts printing to `out`, writing any header the format needs.
+    /// `root` is only used to draw the graph for [Format::Dot].
+    pub fn new(format: Format, mut out: W, root: &HttpStorkable) -> Result<Self> {
+        let out = match format {
+            Format::Csv => {
+                let mut csv = csv::Writer::from_writer(out);
+                csv.write_record(["url", "text", "depth", "parent", "status"])?;
+                Out::Csv(Box::new(csv))
+            }
+            Format::Dot => {
+                // links found several times on the same page are drawn
+                // as several edges, which a strict graph merges
+                writeln!(out, "strict digraph stork {{")?;
+                writeln!(out, "    {};", dot_id(root.val().url().as_str()))?;
+                Out::Plain(out)
+            }
+            Format::Text | Format::Jsonl => Out::Plain(out),
+        };
+
+        Ok(Self {
+            format,
+            out,
+            pending: BTreeMap::new(),
+            pending_urls: HashMap::new(),
+            found: 0,
+        })
+    }
+
+    /// Prints whatever was `found`, or holds on to it until its status
+    /// is known.
+    pub fn found(&mut self, found: Storked<Link, HttpStorkClient>) -> Result<()> {
+        match (self.format, found) {
+            (Format::Text, Storked::Child(link)) => {
+                if let Out::Plain(out) = &mut self.out {
+                    writeln!(out, "{}", link.val().url())?;
+                }
+            }
+            (Format::Dot, Storked::Child(link)) | (Format::Dot, Storked::Seen(link)) => {
+                if let (Out::Plain(out), Some(parent)) = (&mut self.out, link.parent()) {
+                    writeln!(
+                        out,
+                        "    {} -> {};",
+                        dot_id(parent.val().url().as_str()),
+                        dot_id(link.val().url().as_str())
+                    )?;
+                }
+            }
+            (Format::Jsonl, Storked::Child(link)) | (Format::Csv, Storked::Child(link)) => {
+                self.pending_urls
+                    .insert(link.val().url().clone(), self.found);
+                self.pending.insert(self.found, link);
+                self.found += 1;
+            }
+            (Format::Jsonl, Storked::Fetched(fetched))
+            | (Format::Csv, Storked::Fetched(fetched)) => {
+                let link = self
+                    .pending_urls
+                    .remove(fetched.val().url())
+                    .and_then(|i| self.pending.remove(&i));
+
+                if let Some(link) = link {
+                    let status = fetched.page().map(|page| page.status().as_u16());
+                    self.record(&link, status)?;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Prints any links still waiting on their status, along with
+    /// anything the format needs to finish up.
+    pub fn finish(mut self) -> Result<()> {
+        for link in std::mem::take(&mut self.pending).into_values() {
+            self.record(&link, None)?;
+        }
+
+        match (self.format, self.out) {
+            (Format::Dot, Out::Plain(mut out)) => writeln!(out, "}}")?,
+            (_, Out::Plain(mut out)) => out.flush()?,
+            (_, Out::Csv(mut csv)) => csv.flush()?,
+        }
+
+        Ok(())
+    }
+
+    /// Prints a JSON Lines or CSV record for `link`.
+    fn record(&mut self, link: &HttpStorkable, status: Option<u16>) -> Result<()> {
+        let parent = link.parent().map(|parent| parent.val().url().as_str());
+
+        match &mut self.out {
+            Out::Plain(out) => {
+                let record = serde_json::json!({
+                    "url": link.val().url().as_str(),
+                    "text": link.val().text(),
+                    "depth": depth(link),
+                    "parent": parent,
+                    "status": status,
+                });
+
+                writeln!(out, "{}", record)?;
+            }
+            Out::Csv(csv) => csv.write_record(&[
+                link.val().url().to_string(),
+                link.val().text().unwrap_or_default(),
+                depth(link).to_string(),
+                parent.unwrap_or_default().to_string(),
+                status.map(|s| s.to_string()).unwrap_or_default(),
+            ])?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Gets the depth of the link, counting links found on the origin as
+/// being at 0 to match `--max-depth`.
+fn depth(link: &HttpStorkable) -> usize {
+    link.depth().saturating_sub(1)
+}
+
+/// Quotes `value` for use as a node ID in a DOT graph.
+fn dot_id(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,244 @@
+//! Writes the pages found during a crawl out as a
+//! [sitemap](https://www.sitemaps.org/protocol.html).
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use stork_http::Url;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Most URLs a single sitemap is allowed to list, any more and they're
+/// split between several sitemaps listed in a sitemap index.
+const MAX_URLS_PER_SITEMAP: usize = 50_000;
+
+/// A page to be listed in a sitemap.
+pub struct Entry {
+    pub loc: Url,
+    /// When the page was last modified, in W3C datetime format.
+    pub lastmod: Option<String>,
+}
+
+/// Writes `entries` out as a sitemap at `path`, optionally gzipped in
+/// which case `.gz` is added to the path if it isn't there already.
+///
+/// If there are too many entries for a single sitemap they're split
+/// into `<name>-1.xml`, `<name>-2.xml`... alongside `path` and a
+/// sitemap index listing them is written to `path` instead. The index
+/// expects the sitemaps to be served from the directory at `base`.
+///
+/// Returns the path the sitemap (or index) was written to, and the
+/// number of sitemaps written not including the index.
+pub fn write(
+    path: &Path,
+    base: &Url,
+    entries: &[Entry],
+    gzip: bool,
+) -> io::Result<(PathBuf, usize)> {
+    let path = if gzip && path.extension().is_none_or(|extension| extension != "gz") {
+        let mut path = path.as_os_str().to_owned();
+        path.push(".gz");
+        PathBuf::from(path)
+    } else {
+        path.to_path_buf()
+    };
+
+    if entries.len() <= MAX_URLS_PER_SITEMAP {
+        write_file(&path, gzip, |out| write_urlset(out, entries))?;
+        return Ok((path, 1));
+    }
+
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("sitemap.xml");
+    let stem = file_name.trim_end_matches(".gz").trim_end_matches(".xml");
+    let extension = if gzip { ".xml.gz" } else { ".xml" };
+
+    // the sitemaps are joined on to the base, so it has to end in a `/`
+    // for them to end up within it rather than replacing its last segment
+    let mut base = base.clone();
+    if !base.path().ends_with('/') {
+        base.set_path(&format!("{}/", base.path()));
+    }
+
+    let mut sitemaps = Vec::new();
+    for (i, chunk) in entries.chunks(MAX_URLS_PER_SITEMAP).enumerate() {
+        let name = format!("{}-{}{}", stem, i + 1, extension);
+        write_file(&path.with_file_name(&name), gzip, |out| {
+            write_urlset(out, chunk)
+        })?;
+
+        let loc = base.join(&name).map_err(io::Error::other)?;
+        sitemaps.push(loc);
+    }
+
+    write_file(&path, gzip, |out| write_index(out, &sitemaps))?;
+    Ok((path, sitemaps.len()))
+}
+
+/// Creates the file at `path` and hands it to `f` to write out, gzipping
+/// whatever it writes if asked to.
+fn write_file<F>(path: &Path, gzip: bool, f: F) -> io::Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> io::Result<()>,
+{
+    let file = BufWriter::new(File::create(path)?);
+
+    if gzip {
+        let mut out = GzEncoder::new(file, Compression::default());
+        f(&mut out)?;
+        out.finish()?.flush()
+    } else {
+        let mut out = file;
+        f(&mut out)?;
+        out.flush()
+    }
+}
+
+fn write_urlset(out: &mut dyn Write, entries: &[Entry]) -> io::Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#
+    )?;
+
+    for entry in entries {
+        writeln!(out, "  <url>")?;
+        writeln!(out, "    <loc>{}</loc>", escape(entry.loc.as_str()))?;
+        if let Some(lastmod) = &entry.lastmod {
+            writeln!(out, "    <lastmod>{}</lastmod>", escape(lastmod))?;
+        }
+        writeln!(out, "  </url>")?;
+    }
+
+    writeln!(out, "</urlset>")
+}
+
+fn write_index(out: &mut dyn Write, sitemaps: &[Url]) -> io::Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#
+    )?;
+
+    for sitemap in sitemaps {
+        writeln!(out, "  <sitemap>")?;
+        writeln!(out, "    <loc>{}</loc>", escape(sitemap.as_str()))?;
+        writeln!(out, "  </sitemap>")?;
+    }
+
+    writeln!(out, "</sitemapindex>")
+}
+
+/// Escapes the characters the sitemap protocol requires to be escaped
+/// within XML.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use flate2::read::GzDecoder;
+
+    use std::fs;
+    use std::io::Read;
+
+    /// Creates an empty directory for a test to write its sitemaps to.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("storkcli-sitemap-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entries(count: usize) -> Vec<Entry> {
+        (0..count)
+            .map(|i| Entry {
+                loc: format!("https://example.com/{}", i).parse().unwrap(),
+                lastmod: None,
+            })
+            .collect()
+    }
+
+    fn gunzip(path: &Path) -> String {
+        let mut out = String::new();
+        GzDecoder::new(File::open(path).unwrap())
+            .read_to_string(&mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn single_sitemap() {
+        let dir = temp_dir("single");
+        let base = "https://example.com/".parse().unwrap();
+
+        let (path, written) = write(&dir.join("sitemap.xml"), &base, &entries(2), false).unwrap();
+
+        assert_eq!(path, dir.join("sitemap.xml"));
+        assert_eq!(written, 1);
+        let sitemap = fs::read_to_string(path).unwrap();
+        assert!(sitemap.contains("<loc>https://example.com/1</loc>"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn gzip_adds_extension() {
+        let dir = temp_dir("gzip");
+        let base = "https://example.com/".parse().unwrap();
+
+        let (path, _) = write(&dir.join("sitemap.xml"), &base, &entries(1), true).unwrap();
+        assert_eq!(path, dir.join("sitemap.xml.gz"));
+        assert!(!dir.join("sitemap.xml").exists());
+        assert!(gunzip(&path).contains("<loc>https://example.com/0</loc>"));
+
+        // but isn't doubled up
+        let (path, _) = write(&dir.join("other.xml.gz"), &base, &entries(1), true).unwrap();
+        assert_eq!(path, dir.join("other.xml.gz"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn index_lists_sitemaps_under_base() {
+        let dir = temp_dir("index");
+        let base = "https://example.com/static".parse().unwrap();
+        let entries = entries(MAX_URLS_PER_SITEMAP + 1);
+
+        let (path, written) = write(&dir.join("sitemap.xml"), &base, &entries, true).unwrap();
+
+        assert_eq!(path, dir.join("sitemap.xml.gz"));
+        assert_eq!(written, 2);
+        assert!(dir.join("sitemap-1.xml.gz").exists());
+
+        let index = gunzip(&path);
+        assert!(index.contains("<sitemapindex"));
+        assert!(index.contains("<loc>https://example.com/static/sitemap-1.xml.gz</loc>"));
+        assert!(index.contains("<loc>https://example.com/static/sitemap-2.xml.gz</loc>"));
+
+        let second = gunzip(&dir.join("sitemap-2.xml.gz"));
+        assert!(second.contains("<loc>https://example.com/50000</loc>"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}